@@ -33,6 +33,20 @@ pub struct RSecretdSpec {
     pub resources: Vec<Backend>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// which value is kept when several backends produce the same key, defaults to FirstWins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict_policy: Option<ConflictPolicy>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum ConflictPolicy {
+    /// the value of the first backend in resources is kept
+    #[default]
+    FirstWins,
+    /// the value of the last backend in resources is kept
+    LastWins,
+    /// the secret is not written until the backends produce distinct keys
+    Error,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
@@ -47,27 +61,189 @@ pub struct Backend {
     /// Pulumi secret for the pulumi backend
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pulumi_token: Option<String>,
+
+    /// Secret in the namespace of the rsecret holding the passphrase to decrypt secret outputs of self-managed pulumi stacks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulumi_passphrase_secret_ref: Option<SecretKeyReference>,
+
+    /// Secret in the namespace of the rsecret holding the token of the backend, for consul, 1password and the http bearer auth
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_secret_ref: Option<SecretKeyReference>,
+
+    /// Wait time of consul blocking queries eg 5s, the keys are watched outside of the reconcile
+    /// and the rsecret is reconciled as soon as they change, at most half of the backend timeout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consul_wait: Option<String>,
+
+    /// Request of the http backend, the url is the value of the data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpRequest>,
+
+    /// Sidecar provider resolving the external backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external: Option<ExternalProvider>,
+
+    /// Rules rewriting the keys of the backend, applied in order before merging the backends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewrite: Option<Vec<KeyRewrite>>,
+}
+
+/// a rewrite rule, the steps set in a rule are applied in the order
+/// include, exclude, regexp, case, prefix and suffix
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct KeyRewrite {
+    /// only the keys matching the regex are kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<String>,
+
+    /// the keys matching the regex are dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+
+    /// replace the matches of a regex in the keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regexp: Option<RegexpRewrite>,
+
+    /// convert the keys to a case
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case: Option<KeyCase>,
+
+    /// prepended to the keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// appended to the keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RegexpRewrite {
+    /// regex matched against the key
+    pub source: String,
+
+    /// replacement, $1 or ${name} refer to the capture groups
+    pub target: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum KeyCase {
+    /// DbEndpoint, db-endpoint and dbEndpoint become DB_ENDPOINT
+    UpperSnakeCase,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub struct ExternalProvider {
+    /// name of the provider, served on the unix socket PROVIDER_SOCKET_DIR/name.sock
+    pub name: String,
+
+    /// seconds to wait for the provider, defaults to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+pub struct HttpRequest {
+    /// defaults to GET
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<HttpMethod>,
+
+    /// body of POST requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<HttpHeader>,
+
+    /// basic auth, the password is read from a secret in the namespace of the rsecret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_auth: Option<HttpBasicAuth>,
+
+    /// PEM bundle of certificate authorities trusted in addition to the system ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, Hash, JsonSchema)]
+pub enum HttpMethod {
+    #[default]
+    GET,
+    POST,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct HttpHeader {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+
+    /// read the value from a secret in the namespace of the rsecret
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<SecretKeyReference>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+pub struct HttpBasicAuth {
+    pub username: String,
+    pub password_secret_ref: SecretKeyReference,
+}
+
+/// Key of a kubernetes secret or config map
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub struct SecretKeyReference {
+    /// name of the secret or config map
+    pub name: String,
+    /// key in the data
+    pub key: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 pub struct SecretData {
     /// remote value of the backend
     /// for ssm / parameter store / vault: name of the key
     /// for cloudformation and pulumi: stack name
+    /// for cloudformation exports: export name
+    /// for cloudformation tags: stack tag selector eg env=prod,team=data
     /// for plaintext: value of the secret
     /// for appconfig: application id
     /// for pulumi: full stack path eg pulumiOriginId/projectName/stackName
+    /// or checkpoint url eg s3://bucket/.pulumi/stacks/projectName/stackName.json
+    /// for terraform: state location eg s3://bucket/terraform.tfstate or https://host/state
+    /// for s3: object url eg s3://bucket/key
+    /// for kms: base64 ciphertext
+    /// for ecr authorization token: registry account id or registry host
+    /// for azure key vault: object url or secrets|keys|certificates/name[/version]
+    /// for gcp secret manager: secret name or projects/{project}/secrets/{name}[/versions/{version}]
+    /// for consul: key, or key prefix ending with / to read the keys under it recursively
+    /// for 1password: vault name and item title eg Infra/Database
+    /// for http: url of the request
+    /// for kubernetes secret and config map: name, or namespace/name of a namespace in SOURCE_NAMESPACES
+    /// for sops: encrypted yaml, json or dotenv document
+    /// for generator: secret field name of the generated value, unless key is set
+    /// for external: passed as is to the provider
     pub value: String,
 
     /// whether the remote data is jsonstrinified string or not
+    /// deprecated, same as format Json
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_json_string: Option<bool>,
 
+    /// format of the remote data, takes precedence over is_json_string
+    /// yaml, toml, dotenv, properties and ini documents are mapped or queried with remote_path like json
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<DataFormat>,
+
     /// path for the remote data, if remote value is a json
     /// for cloudformation and pulumi should be the outputs path
+    /// for kubernetes secret and config map: key in the source object
     #[serde(skip_serializing_if = "Option::is_none")]
     pub remote_path: Option<String>,
 
+    /// how the remote_path of json remote data is read, defaults to DotPath
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_mode: Option<PathMode>,
+
     /// secret field name
     #[serde(skip_serializing_if = "Option::is_none")]
     pub key: Option<String>,
@@ -79,9 +255,162 @@ pub struct SecretData {
     /// version number for the Hosted configuration versions for appconfig
     #[serde(skip_serializing_if = "Option::is_none")]
     pub version_number: Option<i32>,
+
+    /// aws region of the remote value, defaults to the controller region
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// how the cloudformation value is resolved, defaults to Stack
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloudformation_source: Option<CloudformationSource>,
+
+    /// whether to include the outputs of nested stacks for cloudformation
+    /// nested outputs are keyed as nestedStackLogicalId.OutputKey
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested_stacks: Option<bool>,
+
+    /// version id of the remote object for s3 and gcp secret manager, defaults to the latest version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+
+    /// encryption context the kms ciphertext was encrypted with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_context: Option<BTreeMap<String, String>>,
+
+    /// config map in the namespace of the rsecret holding the sops document instead of value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_map_ref: Option<SecretKeyReference>,
+
+    /// what the generator backend generates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator: Option<GeneratorSpec>,
+
+    /// how a json object without key is mapped, nested values are flattened into dotted keys by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flatten: Option<JsonFlatten>,
+
+    /// how the fetched values are decoded before they are written to the secret, defaults to None
+    /// not supported by the Generator, ECRAuthToken and External backends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoding_strategy: Option<DecodingStrategy>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct JsonFlatten {
+    /// joins the keys of nested objects and the indexes of arrays, defaults to .
+    /// made of the characters of secret keys, alphanumerics, -, _ and .
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(pattern(r"^[-._a-zA-Z0-9]*$"))]
+    pub separator: Option<String>,
+
+    /// keep nested objects and arrays as json strings under their top level key instead of flattening them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nested_as_json: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub struct GeneratorSpec {
+    /// kind of the generated value
+    pub kind: GeneratorKind,
+
+    /// length of the password in characters or of the random bytes, defaults to 32, at most 4096
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(max = 4096))]
+    pub length: Option<u32>,
+
+    /// size of the rsa key in bits, 2048, 3072 or 4096, defaults to 4096
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 2048, max = 4096))]
+    pub bits: Option<u32>,
+
+    /// characters the password is drawn from, defaults to the characters of the classes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+
+    /// classes the password has at least one character of, defaults to all of them without a charset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<CharacterClass>>,
+
+    /// the generated value is kept in the secret until rotation changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub enum GeneratorKind {
+    /// random password
+    Password,
+    /// random version 4 uuid
+    UUID,
+    /// pkcs8 pem rsa private key, the pem public key is stored under key.pub
+    RSA,
+    /// pkcs8 pem ed25519 private key, the pem public key is stored under key.pub
+    Ed25519,
+    /// random bytes
+    Bytes,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digits,
+    Symbols,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum PathMode {
+    /// dotted keys and indexes eg address.street or phones.0
+    #[default]
+    DotPath,
+    /// jsonpath expression with filters, wildcards and slices eg $.users[?(@.role == 'admin')].name
+    JsonPath,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum DecodingStrategy {
+    /// the values are written as fetched
+    #[default]
+    None,
+    /// standard base64, padded or not, line breaks are ignored
+    Base64,
+    /// url safe base64, padded or not
+    Base64Url,
+    /// hexadecimal, upper or lower case
+    Hex,
+    /// base64 then url safe base64 is tried, the value is written as fetched when neither decodes it
+    Auto,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum DataFormat {
+    /// the remote data is used as is
+    Raw,
+    /// jsonstrinified object, same as is_json_string
+    Json,
+    /// yaml document
+    Yaml,
+    /// toml document, datetimes are read as text
+    Toml,
+    /// dotenv document of KEY=value lines
+    Dotenv,
+    /// java properties document, the keys are read as is eg db.host
+    Properties,
+    /// ini document, the keys of a section are read as section.key
+    Ini,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub enum CloudformationSource {
+    /// outputs of the stack named by value
+    Stack,
+    /// value of the export named by value, read via ListExports
+    Export,
+    /// outputs of all stacks matching the tag selector in value
+    Tags,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub enum BackendType {
     SSM,
     SecretManager,
@@ -90,16 +419,153 @@ pub enum BackendType {
     Pulumi,
     Plaintext,
     Vault,
+    Terraform,
+    S3,
+    KMS,
+    ECRAuthToken,
+    AzureKeyVault,
+    GCPSecretManager,
+    Consul,
+    OnePassword,
+    Http,
+    KubernetesSecret,
+    ConfigMap,
+    Sops,
+    Generator,
+    External,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
 pub struct RSecretStatus {
     pub last_updated: Option<DateTime<Utc>>,
+
+    /// keys produced by more than one backend at the last reconcile
+    #[serde(default)]
+    pub conflicts: Vec<KeyConflict>,
+
+    /// indexes of the resources that timed out at the last reconcile, in the order of the spec
+    #[serde(default)]
+    pub timeouts: Vec<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct KeyConflict {
+    /// key of the secret
+    pub key: String,
+
+    /// indexes of the resources producing the key, in the order of the spec
+    pub resources: Vec<usize>,
+
+    /// indexes of the entries producing the key, in the order of the data of the resource,
+    /// when the key is produced more than once within a single resource
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entries: Vec<usize>,
+}
+
+/// Pushes keys of a kubernetes secret to a remote backend
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    kind = "PushSecret",
+    group = "jerry153fish.com",
+    version = "v1beta1",
+    namespaced
+)]
+#[kube(status = "PushSecretStatus")]
+pub struct PushSecretSpec {
+    /// name of the kubernetes secret in the namespace of the push secret
+    pub secret_name: String,
+
+    /// Remote backend the keys are written to
+    pub backend: PushBackendType,
+
+    /// keys of the secret and where they are written to
+    #[serde(default)]
+    pub data: Vec<PushSecretData>,
+
+    /// whether the remote values are deleted with the push secret, defaults to Retain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion_policy: Option<DeletionPolicy>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub enum PushBackendType {
+    /// SecureString parameter of the parameter store
+    SSM,
+    SecretManager,
+    /// value field of the kv v2 secret
+    Vault,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum DeletionPolicy {
+    /// the remote values are kept
+    #[default]
+    Retain,
+    /// the remote values are deleted
+    Delete,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, JsonSchema)]
+pub struct PushSecretData {
+    /// key of the kubernetes secret
+    pub key: String,
+
+    /// ssm parameter name, secret manager secret name or vault secret path the value is written to
+    pub remote_path: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+pub struct PushSecretStatus {
+    pub last_updated: Option<DateTime<Utc>>,
+
+    /// hash of the pushed values, the values are only pushed again when it changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_id: Option<String>,
+
+    /// remote paths of the last push, with the Delete deletion policy the paths no longer pushed are deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_paths: Option<Vec<String>>,
+
+    /// backend of the last push
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<PushBackendType>,
+}
+
+/// a key produced by more than one entry of a backend
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryConflict {
+    pub key: String,
+    /// indexes of the entries producing the key, in the order of the backend data
+    pub entries: Vec<usize>,
+}
+
+/// values resolved by a backend, with the keys produced by more than one of its entries
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RemoteData {
+    pub data: BTreeMap<String, ByteString>,
+    pub conflicts: Vec<EntryConflict>,
+}
+
+impl From<BTreeMap<String, ByteString>> for RemoteData {
+    fn from(data: BTreeMap<String, ByteString>) -> Self {
+        RemoteData {
+            data,
+            conflicts: vec![],
+        }
+    }
 }
 
 #[async_trait]
 pub trait RemoteValue {
-    async fn get_value(&self) -> BTreeMap<String, ByteString>;
+    async fn get_value(&self) -> RemoteData;
+
+    /// when the values should be resolved again, eg before an authorization token expires,
+    /// None when the backend is polled
+    fn refresh_at(&self) -> Option<std::time::SystemTime> {
+        None
+    }
 
-    fn from_backend(backend: &Backend) -> Self;
+    fn from_backend(backend: &Backend) -> Self
+    where
+        Self: Sized;
 }
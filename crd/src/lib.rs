@@ -6,8 +6,35 @@
 pub mod crd;
 pub use crd::Backend;
 pub use crd::BackendType;
+pub use crd::CharacterClass;
+pub use crd::CloudformationSource;
+pub use crd::ConflictPolicy;
+pub use crd::DataFormat;
+pub use crd::DecodingStrategy;
+pub use crd::DeletionPolicy;
+pub use crd::EntryConflict;
+pub use crd::ExternalProvider;
+pub use crd::GeneratorKind;
+pub use crd::GeneratorSpec;
+pub use crd::HttpBasicAuth;
+pub use crd::HttpHeader;
+pub use crd::HttpMethod;
+pub use crd::HttpRequest;
+pub use crd::JsonFlatten;
+pub use crd::KeyCase;
+pub use crd::KeyConflict;
+pub use crd::KeyRewrite;
+pub use crd::PathMode;
+pub use crd::PushBackendType;
+pub use crd::PushSecret;
+pub use crd::PushSecretData;
+pub use crd::PushSecretSpec;
+pub use crd::PushSecretStatus;
 pub use crd::RSecret;
 pub use crd::RSecretStatus;
 pub use crd::RSecretdSpec;
+pub use crd::RegexpRewrite;
+pub use crd::RemoteData;
 pub use crd::RemoteValue;
 pub use crd::SecretData;
+pub use crd::SecretKeyReference;
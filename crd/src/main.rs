@@ -1,4 +1,8 @@
 use kube::CustomResourceExt;
 fn main() {
-    print!("{}", serde_yaml::to_string(&crd::RSecret::crd()).unwrap())
+    print!("{}", serde_yaml::to_string(&crd::RSecret::crd()).unwrap());
+    print!(
+        "---\n{}",
+        serde_yaml::to_string(&crd::PushSecret::crd()).unwrap()
+    )
 }
@@ -27,6 +27,28 @@ aws secretsmanager put-secret-value \
   --secret-id MyJsonSecret \
   --secret-string '{ "srmName": "test", "srmTest": "objectName"}'
 
+aws s3 mb s3://remote-secrets-test || true
+
+cat > /tmp/terraform.tfstate <<'EOF'
+{
+  "version": 4,
+  "outputs": {
+    "bucket_name": { "value": "my-bucket", "type": "string" },
+    "database": {
+      "value": { "host": "db.internal", "port": 5432 },
+      "type": ["object", { "host": "string", "port": "number" }],
+      "sensitive": true
+    }
+  }
+}
+EOF
+
+aws s3 cp /tmp/terraform.tfstate s3://remote-secrets-test/terraform.tfstate
+
+printf 'S3_ENV_NAME=s3Env\nS3_ENV_TEST=objectName\n' > /tmp/app.env
+
+aws s3 cp /tmp/app.env s3://remote-secrets-test/app.env
+
 aws cloudformation create-stack \
   --stack-name MyTestStack \
   --template-body file:///etc/floci/templates/mock-cfn.yaml || \
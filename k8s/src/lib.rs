@@ -1 +1,2 @@
+pub mod push_secret;
 pub mod secret;
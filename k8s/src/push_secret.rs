@@ -0,0 +1,308 @@
+use crd::{DeletionPolicy, PushBackendType, PushSecret, PushSecretStatus};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::{Patch, PatchParams};
+use kube::{Api, Client};
+#[cfg(feature = "aws")]
+use plugins::aws_secret_manager::{delete_secretsmanager_secret, put_secretsmanager_value};
+#[cfg(feature = "aws")]
+use plugins::aws_ssm::{delete_ssm_parameter, put_ssm_parameter};
+#[cfg(feature = "vault")]
+use plugins::vault::{delete_vault_value, put_vault_value};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::secret::calculate_hash;
+
+pub const PUSH_SECRET_FINALIZER: &str = "pushsecrets.jerry153fish.com/finalizer";
+
+/// the values of the selected keys of the secret by remote path
+pub fn collect_push_data(
+    push_secret: &PushSecret,
+    secret: &Secret,
+) -> Result<BTreeMap<String, String>> {
+    let secret_data = secret.data.clone().unwrap_or_default();
+    let mut data = BTreeMap::new();
+
+    for push_data in push_secret.spec.data.iter() {
+        let value = secret_data.get(&push_data.key).ok_or_else(|| {
+            anyhow!(
+                "no key {} found in the secret {}",
+                push_data.key,
+                push_secret.spec.secret_name
+            )
+        })?;
+        let value = String::from_utf8(value.0.clone())
+            .map_err(|_| anyhow!("the value of the key {} is not utf-8", push_data.key))?;
+
+        data.insert(push_data.remote_path.clone(), value);
+    }
+
+    Ok(data)
+}
+
+/// hash of the values and where they are pushed to, the values are pushed again when it changes
+pub fn calculate_push_hash(backend: &PushBackendType, data: &BTreeMap<String, String>) -> u64 {
+    calculate_hash(&(backend, data))
+}
+
+/// whether the push secret needs pushing, the hash of the last push is kept in the status
+pub fn is_push_needed(push_secret: &PushSecret, hash_id: u64) -> bool {
+    push_secret
+        .status
+        .as_ref()
+        .and_then(|status| status.hash_id.as_ref())
+        != Some(&hash_id.to_string())
+}
+
+/// write every value to the remote path of the backend
+#[cfg_attr(
+    not(any(feature = "aws", feature = "vault")),
+    allow(unused_variables, clippy::never_loop)
+)]
+pub async fn push_data(backend: &PushBackendType, data: &BTreeMap<String, String>) -> Result<()> {
+    for (remote_path, value) in data.iter() {
+        match backend {
+            #[cfg(feature = "aws")]
+            PushBackendType::SSM => put_ssm_parameter(remote_path, value).await?,
+            #[cfg(feature = "aws")]
+            PushBackendType::SecretManager => put_secretsmanager_value(remote_path, value).await?,
+            #[cfg(feature = "vault")]
+            PushBackendType::Vault => put_vault_value(remote_path, value).await?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(anyhow!("pushing to {backend:?} is not compiled in")),
+        }
+    }
+
+    Ok(())
+}
+
+/// whether the remote values are deleted, with the push secret or once they are no longer pushed
+pub fn is_delete_policy(push_secret: &PushSecret) -> bool {
+    push_secret.spec.deletion_policy.clone().unwrap_or_default() == DeletionPolicy::Delete
+}
+
+/// the remote paths of the last push recorded in the status, with the backend they were pushed to
+fn get_pushed_paths(push_secret: &PushSecret) -> Vec<(PushBackendType, String)> {
+    let Some(status) = &push_secret.status else {
+        return vec![];
+    };
+    let backend = status
+        .backend
+        .clone()
+        .unwrap_or(push_secret.spec.backend.clone());
+
+    status
+        .remote_paths
+        .iter()
+        .flatten()
+        .map(|remote_path| (backend.clone(), remote_path.clone()))
+        .collect()
+}
+
+/// the remote paths of the last push which are no longer pushed,
+/// eg after a rename of the remote path or a change of the backend
+pub fn find_stale_paths(
+    push_secret: &PushSecret,
+    data: &BTreeMap<String, String>,
+) -> Vec<(PushBackendType, String)> {
+    get_pushed_paths(push_secret)
+        .into_iter()
+        .filter(|(backend, remote_path)| {
+            backend != &push_secret.spec.backend || !data.contains_key(remote_path)
+        })
+        .collect()
+}
+
+/// delete the values of the remote paths, missing values are ignored
+#[cfg_attr(
+    not(any(feature = "aws", feature = "vault")),
+    allow(unused_variables, clippy::never_loop)
+)]
+pub async fn delete_remote_paths(paths: &[(PushBackendType, String)]) -> Result<()> {
+    for (backend, remote_path) in paths.iter() {
+        match backend {
+            #[cfg(feature = "aws")]
+            PushBackendType::SSM => delete_ssm_parameter(remote_path).await?,
+            #[cfg(feature = "aws")]
+            PushBackendType::SecretManager => delete_secretsmanager_secret(remote_path).await?,
+            #[cfg(feature = "vault")]
+            PushBackendType::Vault => delete_vault_value(remote_path).await?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(anyhow!("pushing to {backend:?} is not compiled in")),
+        }
+    }
+
+    Ok(())
+}
+
+/// delete the remote values when the deletion policy is Delete,
+/// the paths of the last push and the paths of the spec which may not have been pushed completely
+pub async fn delete_remote_data(push_secret: &PushSecret) -> Result<()> {
+    if !is_delete_policy(push_secret) {
+        return Ok(());
+    }
+
+    let mut paths = get_pushed_paths(push_secret);
+    for push_data in push_secret.spec.data.iter() {
+        let path = (
+            push_secret.spec.backend.clone(),
+            push_data.remote_path.clone(),
+        );
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    delete_remote_paths(&paths).await
+}
+
+/// whether the push secret pushes the keys of the secret namespace/name
+pub fn references_secret(push_secret: &PushSecret, namespace: &str, name: &str) -> bool {
+    push_secret.metadata.namespace.as_deref() == Some(namespace)
+        && push_secret.spec.secret_name == name
+}
+
+/// Adds the finalizer of the push secret, so the remote values can be deleted with it
+pub async fn add(client: Client, name: &str, namespace: &str) -> Result<PushSecret, kube::Error> {
+    let api: Api<PushSecret> = Api::namespaced(client, namespace);
+    let finalizer: Value = json!({
+        "metadata": {
+            "finalizers": [PUSH_SECRET_FINALIZER]
+        }
+    });
+
+    let patch: Patch<&Value> = Patch::Merge(&finalizer);
+    api.patch(name, &PatchParams::default(), &patch).await
+}
+
+/// Removes all finalizers of the push secret
+pub async fn delete(
+    client: Client,
+    name: &str,
+    namespace: &str,
+) -> Result<PushSecret, kube::Error> {
+    let api: Api<PushSecret> = Api::namespaced(client, namespace);
+    let finalizer: Value = json!({
+        "metadata": {
+            "finalizers": null
+        }
+    });
+
+    let patch: Patch<&Value> = Patch::Merge(&finalizer);
+    api.patch(name, &PatchParams::default(), &patch).await
+}
+
+/// record the last push in the status
+pub async fn update_status(
+    client: Client,
+    name: &str,
+    namespace: &str,
+    status: &PushSecretStatus,
+) -> Result<PushSecret, kube::Error> {
+    let api: Api<PushSecret> = Api::namespaced(client, namespace);
+    let status: Value = json!({ "status": status });
+
+    let patch: Patch<&Value> = Patch::Merge(&status);
+    api.patch_status(name, &PatchParams::default(), &patch)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crd::{PushSecretData, PushSecretSpec};
+    use k8s_openapi::ByteString;
+
+    fn sample_push_secret() -> PushSecret {
+        let spec = PushSecretSpec {
+            secret_name: "db-tls".into(),
+            backend: PushBackendType::SSM,
+            data: vec![PushSecretData {
+                key: "tls.crt".into(),
+                remote_path: "/apps/db/tls.crt".into(),
+            }],
+            deletion_policy: None,
+        };
+
+        let mut push_secret = PushSecret::new("db-tls", spec);
+        push_secret.metadata.namespace = Some("apps".into());
+        push_secret
+    }
+
+    fn sample_secret(value: &[u8]) -> Secret {
+        Secret {
+            data: Some(BTreeMap::from([
+                ("tls.crt".to_string(), ByteString(value.to_vec())),
+                ("tls.key".to_string(), ByteString(b"private".to_vec())),
+            ])),
+            ..Secret::default()
+        }
+    }
+
+    #[test]
+    fn collects_selected_keys_by_remote_path() {
+        let push_secret = sample_push_secret();
+        let data = collect_push_data(&push_secret, &sample_secret(b"certificate")).unwrap();
+
+        assert_eq!(
+            data,
+            BTreeMap::from([("/apps/db/tls.crt".to_string(), "certificate".to_string())])
+        );
+        assert!(collect_push_data(&push_secret, &Secret::default()).is_err());
+        assert!(collect_push_data(&push_secret, &sample_secret(&[0xff, 0xfe])).is_err());
+    }
+
+    #[test]
+    fn pushes_only_when_the_hash_changes() {
+        let mut push_secret = sample_push_secret();
+        let data = collect_push_data(&push_secret, &sample_secret(b"certificate")).unwrap();
+        let hash_id = calculate_push_hash(&push_secret.spec.backend, &data);
+        assert!(is_push_needed(&push_secret, hash_id));
+
+        push_secret.status = Some(PushSecretStatus {
+            hash_id: Some(hash_id.to_string()),
+            ..PushSecretStatus::default()
+        });
+        assert!(!is_push_needed(&push_secret, hash_id));
+
+        let changed = collect_push_data(&push_secret, &sample_secret(b"renewed")).unwrap();
+        let changed_hash_id = calculate_push_hash(&push_secret.spec.backend, &changed);
+        assert!(is_push_needed(&push_secret, changed_hash_id));
+        assert_ne!(calculate_push_hash(&PushBackendType::Vault, &data), hash_id);
+    }
+
+    #[test]
+    fn finds_the_paths_no_longer_pushed() {
+        let mut push_secret = sample_push_secret();
+        let data = collect_push_data(&push_secret, &sample_secret(b"certificate")).unwrap();
+        assert!(find_stale_paths(&push_secret, &data).is_empty());
+
+        push_secret.status = Some(PushSecretStatus {
+            remote_paths: Some(vec![
+                "/apps/db/tls.crt".to_string(),
+                "/apps/db/old-tls.crt".to_string(),
+            ]),
+            backend: Some(PushBackendType::SSM),
+            ..PushSecretStatus::default()
+        });
+        assert_eq!(
+            find_stale_paths(&push_secret, &data),
+            vec![(PushBackendType::SSM, "/apps/db/old-tls.crt".to_string())]
+        );
+
+        // every path of the previous backend is stale once the backend changes
+        push_secret.spec.backend = PushBackendType::Vault;
+        assert_eq!(find_stale_paths(&push_secret, &data).len(), 2);
+    }
+
+    #[test]
+    fn matches_the_pushed_secret() {
+        let push_secret = sample_push_secret();
+
+        assert!(references_secret(&push_secret, "apps", "db-tls"));
+        assert!(!references_secret(&push_secret, "default", "db-tls"));
+        assert!(!references_secret(&push_secret, "apps", "db-creds"));
+    }
+}
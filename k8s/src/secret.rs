@@ -1,43 +1,299 @@
-use crd::{Backend, BackendType, RSecret, RemoteValue, SecretData};
+use crd::{
+    Backend, BackendType, ConflictPolicy, EntryConflict, KeyConflict, RSecret, RSecretStatus,
+    RemoteData, SecretData,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use futures::future::join_all;
 use k8s_openapi::{api::core::v1::Secret, ByteString};
 use kube::{
     api::{DeleteParams, Patch, PatchParams, PostParams},
     core::ObjectMeta,
 };
 use kube::{Api, Client};
-use plugins::aws_cfn::Cloudformation;
-use plugins::aws_secret_manager::SecretManager;
-use plugins::aws_ssm::SSM;
-use plugins::plaintext::PlainText;
-use plugins::pulumi::Pulumi;
-use plugins::vault::Vault;
+use plugins::concurrent::get_backend_timeout;
+use plugins::generator::get_rotation_annotations;
+use plugins::kubernetes::parse_source_ref;
+use plugins::registry::{backend_name, get_provider, BackendContext};
 use serde_json::{json, Value};
 use std::collections::{hash_map::DefaultHasher, BTreeMap};
 use std::hash::{Hash, Hasher};
-use utils::value::{get_json_string_nested_value, merge_secret_data};
+use std::time::{Duration, SystemTime};
+use utils::rewrite::rewrite_keys;
+use utils::value::{get_secret_data, merge_secret_data};
+
+/// the rsecrets are resolved again after this time, unless every backend has a refresh time
+pub const DEFAULT_REQUEUE_AFTER: Duration = Duration::from_secs(20);
+
+/// secret data of a rsecret, with the keys produced by more than one backend
+#[derive(Clone, Debug, Default)]
+pub struct CollectedSecretData {
+    pub data: BTreeMap<String, ByteString>,
+    pub conflicts: Vec<KeyConflict>,
+    /// indexes of the resources that timed out
+    pub timeouts: Vec<usize>,
+    /// when the rsecret should be resolved again
+    pub requeue_after: Duration,
+}
+
+/// secret data of a backend, with when it should be resolved again
+#[derive(Clone, Debug, Default)]
+pub struct BackendData {
+    pub data: BTreeMap<String, ByteString>,
+    pub refresh_at: Option<SystemTime>,
+    pub timed_out: bool,
+    /// keys produced by more than one entry of the backend, after the rewrite
+    pub entry_conflicts: Vec<EntryConflict>,
+}
 
-pub async fn collect_secret_data(rsecret: &RSecret) -> BTreeMap<String, ByteString> {
+/// existing is the secret of the rsecret, if any, holding the values of the generator backends
+pub async fn collect_secret_data(
+    client: &Client,
+    rsecret: &RSecret,
+    existing: Option<&Secret>,
+) -> CollectedSecretData {
     let mut secrets = BTreeMap::new();
+    let namespace = rsecret
+        .metadata
+        .namespace
+        .clone()
+        .unwrap_or_else(|| "default".to_owned());
+    let name = rsecret.metadata.name.clone().unwrap_or_default();
+    let context = BackendContext {
+        namespace: &namespace,
+        name: &name,
+        existing,
+        client,
+    };
+    let timeout = get_backend_timeout();
+
+    let backends = rsecret.spec.resources.iter().map(|backend| async move {
+        let resolve = resolve_backend_data(backend, &context);
+        match tokio::time::timeout(timeout, resolve).await {
+            Ok(backend_data) => backend_data,
+            Err(_) => {
+                log::error!(
+                    "backend {} timed out after {}s",
+                    backend_name(&backend.backend),
+                    timeout.as_secs()
+                );
+                BackendData {
+                    timed_out: true,
+                    ..BackendData::default()
+                }
+            }
+        }
+    });
 
-    for backend in rsecret.spec.resources.iter() {
-        let backend_data = resolve_backend_data(backend).await;
-        secrets = merge_secret_data(backend_data, secrets);
+    // join_all keeps the order of the resources, so the merge below is the same as resolving
+    // them one after the other, the requests of the backends share the concurrency limit
+    let results: Vec<BackendData> = join_all(backends).await;
+
+    let timeouts: Vec<usize> = results
+        .iter()
+        .enumerate()
+        .filter(|(_, backend)| backend.timed_out)
+        .map(|(index, _)| index)
+        .collect();
+    let refresh_times: Vec<Option<SystemTime>> =
+        results.iter().map(|backend| backend.refresh_at).collect();
+    let conflicts = find_key_conflicts(&results);
+    let results: Vec<BTreeMap<String, ByteString>> =
+        results.into_iter().map(|backend| backend.data).collect();
+
+    let policy = rsecret.spec.conflict_policy.clone().unwrap_or_default();
+    for backend_data in results {
+        secrets = match policy {
+            ConflictPolicy::LastWins => merge_secret_data(secrets, backend_data),
+            ConflictPolicy::FirstWins | ConflictPolicy::Error => {
+                merge_secret_data(backend_data, secrets)
+            }
+        };
     }
 
-    secrets
+    CollectedSecretData {
+        data: secrets,
+        conflicts,
+        timeouts,
+        requeue_after: get_requeue_after(&refresh_times, SystemTime::now()),
+    }
+}
+
+/// the earliest refresh time of the backends, polled every DEFAULT_REQUEUE_AFTER otherwise
+pub fn get_requeue_after(refresh_times: &[Option<SystemTime>], now: SystemTime) -> Duration {
+    let earliest = refresh_times.iter().flatten().min().map(|refresh_at| {
+        refresh_at
+            .duration_since(now)
+            .unwrap_or_default()
+            .max(Duration::from_secs(1))
+    });
+    let polled = refresh_times.is_empty() || refresh_times.iter().any(Option::is_none);
+
+    match earliest {
+        Some(earliest) if polled => earliest.min(DEFAULT_REQUEUE_AFTER),
+        Some(earliest) => earliest,
+        None => DEFAULT_REQUEUE_AFTER,
+    }
 }
 
-async fn resolve_backend_data(backend: &Backend) -> BTreeMap<String, ByteString> {
-    match backend.backend {
-        BackendType::Plaintext => PlainText::from_backend(backend).get_value().await,
-        BackendType::SecretManager => SecretManager::from_backend(backend).get_value().await,
-        BackendType::SSM => SSM::from_backend(backend).get_value().await,
-        BackendType::Cloudformation => Cloudformation::from_backend(backend).get_value().await,
-        BackendType::Pulumi => Pulumi::from_backend(backend).get_value().await,
-        BackendType::Vault => Vault::from_backend(backend).get_value().await,
-        _ => BTreeMap::new(),
+/// the keys produced by more than one backend, then by more than one entry of a backend
+pub fn find_key_conflicts(backends: &[BackendData]) -> Vec<KeyConflict> {
+    let mut resources_by_key: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, backend) in backends.iter().enumerate() {
+        for key in backend.data.keys() {
+            resources_by_key.entry(key).or_default().push(index);
+        }
+    }
+
+    let conflicts = resources_by_key
+        .into_iter()
+        .filter(|(_, resources)| resources.len() > 1)
+        .map(|(key, resources)| KeyConflict {
+            key: key.to_owned(),
+            resources,
+            entries: vec![],
+        });
+    let entry_conflicts = backends.iter().enumerate().flat_map(|(index, backend)| {
+        backend
+            .entry_conflicts
+            .iter()
+            .map(move |conflict| KeyConflict {
+                key: conflict.key.clone(),
+                resources: vec![index],
+                entries: conflict.entries.clone(),
+            })
+    });
+
+    conflicts.chain(entry_conflicts).collect()
+}
+
+/// one line per conflicting key with the backends producing it
+pub fn describe_key_conflicts(rsecret: &RSecret, conflicts: &[KeyConflict]) -> String {
+    conflicts
+        .iter()
+        .map(|conflict| {
+            let backends: Vec<String> = conflict
+                .resources
+                .iter()
+                .map(|index| match rsecret.spec.resources.get(*index) {
+                    Some(backend) => format!("{} ({index})", backend_name(&backend.backend)),
+                    None => index.to_string(),
+                })
+                .collect();
+            if conflict.entries.is_empty() {
+                return format!("key {} produced by {}", conflict.key, backends.join(", "));
+            }
+            if let [entry] = conflict.entries.as_slice() {
+                return format!(
+                    "key {} produced more than once by the entry {entry} of {}",
+                    conflict.key,
+                    backends.join(", ")
+                );
+            }
+            let entries: Vec<String> = conflict.entries.iter().map(usize::to_string).collect();
+            format!(
+                "key {} produced by the entries {} of {}",
+                conflict.key,
+                entries.join(", "),
+                backends.join(", ")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// the conflicts are an error with the Error conflict policy, the secret is then left untouched
+pub fn check_key_conflicts(rsecret: &RSecret, conflicts: &[KeyConflict]) -> Result<()> {
+    if conflicts.is_empty()
+        || rsecret.spec.conflict_policy.clone().unwrap_or_default() != ConflictPolicy::Error
+    {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "keys produced by more than one backend: {}",
+        describe_key_conflicts(rsecret, conflicts).replace('\n', "; ")
+    ))
+}
+
+/// one line per resource that timed out
+pub fn describe_backend_timeouts(rsecret: &RSecret, timeouts: &[usize]) -> String {
+    timeouts
+        .iter()
+        .map(|index| {
+            let backend = match rsecret.spec.resources.get(*index) {
+                Some(backend) => format!("{} ({index})", backend_name(&backend.backend)),
+                None => index.to_string(),
+            };
+            format!(
+                "backend {backend} timed out after {}s",
+                get_backend_timeout().as_secs()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// whether the conflicts or the timeouts differ from the ones recorded in the status
+pub fn is_status_outdated(rsecret: &RSecret, collected: &CollectedSecretData) -> bool {
+    let (conflicts, timeouts) = rsecret
+        .status
+        .as_ref()
+        .map(|status| (status.conflicts.as_slice(), status.timeouts.as_slice()))
+        .unwrap_or_default();
+
+    conflicts != collected.conflicts || timeouts != collected.timeouts
+}
+
+async fn resolve_backend_data(backend: &Backend, context: &BackendContext<'_>) -> BackendData {
+    let name = backend_name(&backend.backend);
+    let Some(provider) = get_provider(&name) else {
+        log::error!("no provider for the backend {name}, is it compiled in?");
+        return BackendData::default();
+    };
+    if let Err(err) = provider.validate(backend) {
+        log::error!("{err}");
+        return BackendData::default();
+    }
+
+    let remote_value = (provider.factory)(backend, context);
+    let RemoteData {
+        data,
+        conflicts: entry_conflicts,
+    } = remote_value.get_value().await;
+    let refresh_at = remote_value.refresh_at();
+
+    let Some(rules) = &backend.rewrite else {
+        return BackendData {
+            data,
+            refresh_at,
+            entry_conflicts,
+            ..BackendData::default()
+        };
+    };
+    // the rewritten keys are what the other backends are merged with
+    let data = match rewrite_keys(data, rules) {
+        Ok(data) => data,
+        Err(err) => {
+            log::error!("failed to rewrite the keys of the backend {name}: {err}");
+            BTreeMap::new()
+        }
+    };
+    // the conflicts are reported under the keys of the secret
+    let entry_conflicts = entry_conflicts
+        .into_iter()
+        .filter_map(|conflict| {
+            let key = BTreeMap::from([(conflict.key, ByteString(vec![]))]);
+            let key = rewrite_keys(key, rules).ok()?.into_keys().next()?;
+            Some(EntryConflict { key, ..conflict })
+        })
+        .collect();
+
+    BackendData {
+        data,
+        refresh_at,
+        entry_conflicts,
+        ..BackendData::default()
     }
 }
 
@@ -69,6 +325,21 @@ pub async fn delete(client: Client, name: &str, namespace: &str) -> Result<RSecr
     api.patch(name, &PatchParams::default(), &patch).await
 }
 
+/// record the conflicting keys in the status
+pub async fn update_status(
+    client: Client,
+    name: &str,
+    namespace: &str,
+    status: &RSecretStatus,
+) -> Result<RSecret, kube::Error> {
+    let api: Api<RSecret> = Api::namespaced(client, namespace);
+    let status: Value = json!({ "status": status });
+
+    let patch: Patch<&Value> = Patch::Merge(&status);
+    api.patch_status(name, &PatchParams::default(), &patch)
+        .await
+}
+
 /// create a new secret from rsecret
 pub async fn create_k8s_secret(
     client: Client,
@@ -90,9 +361,10 @@ pub async fn create_k8s_secret(
             name: Some(name.clone()),
             namespace: Some(ns.clone()),
             labels: Some(labels),
+            annotations: Some(build_annotations(rsecret)),
             ..ObjectMeta::default()
         },
-        type_: Some("Opaque".to_owned()),
+        type_: Some(secret_type(rsecret).to_owned()),
         data: Some(data.clone()),
         immutable: Some(false),
         ..Secret::default()
@@ -119,7 +391,22 @@ pub async fn update_k8s_secret(
 
     let k8s_secret_api: Api<Secret> = Api::namespaced(client.clone(), &ns);
 
-    if k8s_secret_api.get(&name).await.is_ok() {
+    let existing = k8s_secret_api.get(&name).await;
+    if let Ok(secret) = &existing {
+        if is_secret_type_outdated(rsecret, secret) {
+            // the type of a secret is immutable, the secret is created again with the new type
+            log::info!(
+                "recreating the secret {name} in namespace {ns} as {}",
+                secret_type(rsecret)
+            );
+            k8s_secret_api
+                .delete(&name, &DeleteParams::default())
+                .await?;
+            return create_k8s_secret(client.clone(), rsecret, data).await;
+        }
+    }
+
+    if existing.is_ok() {
         let hash_id = calculate_secret_hash(data);
 
         let data_value = serde_json::to_value(data).map_err(kube::Error::SerdeError)?;
@@ -128,7 +415,8 @@ pub async fn update_k8s_secret(
             "metadata": {
                 "labels": {
                     "hash_id": hash_id.to_string()
-                }
+                },
+                "annotations": build_annotations(rsecret)
             },
             "data": data_value
         });
@@ -179,39 +467,65 @@ pub fn get_hash_id(secret: &Secret) -> Option<u64> {
     hash_id.parse().ok()
 }
 
-// TODO: a better way error handling
+/// the secret data of an entry from the remote data, parsed with the format and path mode of the entry
 pub fn rsecret_data_to_secret_data(
     rsecret_data: &SecretData,
     value_string: &str,
 ) -> BTreeMap<String, ByteString> {
-    let mut secrets = BTreeMap::new();
-    if rsecret_data.key.is_some() {
-        let key = rsecret_data.key.clone().unwrap();
-
-        if rsecret_data.is_json_string.unwrap_or_default() {
-            if rsecret_data.remote_path.is_some() {
-                let value = get_json_string_nested_value(
-                    value_string,
-                    &rsecret_data.remote_path.clone().unwrap(),
-                );
+    get_secret_data(rsecret_data, value_string)
+}
 
-                match value {
-                    Ok(value) => {
-                        if !value.is_empty() {
-                            secrets.insert(key, ByteString(value.as_bytes().to_vec()));
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("{e}");
-                    }
-                }
-            }
-        } else {
-            secrets.insert(key, ByteString(value_string.as_bytes().to_vec()));
-        }
+/// whether the rsecret reads the secret or config map namespace/name through the backend type,
+/// changes of the source object re-trigger the reconcile of the rsecret
+pub fn references_source(
+    rsecret: &RSecret,
+    backend_type: &BackendType,
+    namespace: &str,
+    name: &str,
+) -> bool {
+    let rsecret_namespace = rsecret.metadata.namespace.clone().unwrap_or_default();
+
+    rsecret
+        .spec
+        .resources
+        .iter()
+        .filter(|backend| &backend.backend == backend_type)
+        .flat_map(|backend| backend.data.iter())
+        .any(|secret_data| {
+            parse_source_ref(&secret_data.value, &rsecret_namespace)
+                == (namespace.to_owned(), name.to_owned())
+        })
+}
+
+/// whether the secret has another type than the rsecret renders
+pub fn is_secret_type_outdated(rsecret: &RSecret, secret: &Secret) -> bool {
+    secret.type_.as_deref().unwrap_or("Opaque") != secret_type(rsecret)
+}
+
+/// ecr authorization tokens are rendered into a docker config secret for image pulls
+fn secret_type(rsecret: &RSecret) -> &'static str {
+    let has_ecr_auth_token = rsecret
+        .spec
+        .resources
+        .iter()
+        .any(|backend| matches!(backend.backend, BackendType::ECRAuthToken));
+
+    if has_ecr_auth_token {
+        "kubernetes.io/dockerconfigjson"
+    } else {
+        "Opaque"
     }
+}
 
-    secrets
+/// rotations the generated values of the secret were generated for
+fn build_annotations(rsecret: &RSecret) -> BTreeMap<String, String> {
+    rsecret
+        .spec
+        .resources
+        .iter()
+        .filter(|backend| backend.backend == BackendType::Generator)
+        .flat_map(get_rotation_annotations)
+        .collect()
 }
 
 fn build_labels(name: &str, hash_id: u64) -> BTreeMap<String, String> {
@@ -227,6 +541,13 @@ mod tests {
     use crd::RSecretdSpec;
     use kube::core::ObjectMeta;
 
+    /// client of an unreachable api server, the plaintext backends do not read kubernetes objects
+    fn test_client() -> Client {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let config = kube::Config::new("http://127.0.0.1:1".parse().unwrap());
+        Client::try_from(config).unwrap()
+    }
+
     fn sample_rsecret() -> RSecret {
         let backend = Backend {
             backend: BackendType::Plaintext,
@@ -235,15 +556,21 @@ mod tests {
                 is_json_string: None,
                 remote_path: None,
                 key: Some("plain-key".into()),
-                configuration_profile_id: None,
-                version_number: None,
+                ..SecretData::default()
             }],
             pulumi_token: None,
+            pulumi_passphrase_secret_ref: None,
+            token_secret_ref: None,
+            consul_wait: None,
+            http: None,
+            external: None,
+            rewrite: None,
         };
 
         let spec = RSecretdSpec {
             resources: vec![backend],
             description: None,
+            conflict_policy: None,
         };
 
         let mut rsecret = RSecret::new("example", spec);
@@ -254,12 +581,132 @@ mod tests {
     #[tokio::test]
     async fn collects_plaintext_secret_data() {
         let rsecret = sample_rsecret();
-        let data = collect_secret_data(&rsecret).await;
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        assert!(collected.conflicts.is_empty());
+        let data = collected.data;
         assert_eq!(data.len(), 1);
         let value = data.get("plain-key").expect("missing key");
         assert_eq!(value.0.as_slice(), b"plain-value");
     }
 
+    fn conflicting_rsecret(conflict_policy: Option<ConflictPolicy>) -> RSecret {
+        let mut rsecret = sample_rsecret();
+        for value in ["second-value", "third-value"] {
+            let mut backend = rsecret.spec.resources[0].clone();
+            backend.data[0].value = value.into();
+            backend.data.push(SecretData {
+                value: value.into(),
+                key: Some(value.into()),
+                ..SecretData::default()
+            });
+            rsecret.spec.resources.push(backend);
+        }
+        rsecret.spec.conflict_policy = conflict_policy;
+        rsecret
+    }
+
+    #[tokio::test]
+    async fn keeps_the_precedence_of_the_backends() {
+        let rsecret = conflicting_rsecret(None);
+
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        let data = collected.data;
+        assert_eq!(data.len(), 3);
+        assert_eq!(data["plain-key"].0.as_slice(), b"plain-value");
+        assert_eq!(data["third-value"].0.as_slice(), b"third-value");
+
+        assert_eq!(
+            collected.conflicts,
+            vec![KeyConflict {
+                key: "plain-key".into(),
+                resources: vec![0, 1, 2],
+                entries: vec![],
+            }]
+        );
+        assert!(check_key_conflicts(&rsecret, &collected.conflicts).is_ok());
+    }
+
+    #[tokio::test]
+    async fn reports_the_conflicts_between_entries() {
+        let mut rsecret = sample_rsecret();
+        rsecret.spec.resources[0].data.push(SecretData {
+            value: "other-value".into(),
+            key: Some("plain-key".into()),
+            ..SecretData::default()
+        });
+        rsecret.spec.resources[0].rewrite = Some(vec![crd::KeyRewrite {
+            case: Some(crd::KeyCase::UpperSnakeCase),
+            ..crd::KeyRewrite::default()
+        }]);
+        rsecret.spec.conflict_policy = Some(ConflictPolicy::Error);
+
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        assert_eq!(collected.data["PLAIN_KEY"].0.as_slice(), b"plain-value");
+        assert_eq!(
+            collected.conflicts,
+            vec![KeyConflict {
+                key: "PLAIN_KEY".into(),
+                resources: vec![0],
+                entries: vec![0, 1],
+            }]
+        );
+        let err = check_key_conflicts(&rsecret, &collected.conflicts).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "keys produced by more than one backend: key PLAIN_KEY produced by the entries 0, 1 of Plaintext (0)"
+        );
+    }
+
+    #[tokio::test]
+    async fn rewrites_the_keys_before_merging() {
+        let mut rsecret = conflicting_rsecret(None);
+        rsecret.spec.resources[1].rewrite = Some(vec![crd::KeyRewrite {
+            case: Some(crd::KeyCase::UpperSnakeCase),
+            prefix: Some("SECOND_".into()),
+            ..crd::KeyRewrite::default()
+        }]);
+
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        assert_eq!(
+            collected.data["SECOND_PLAIN_KEY"].0.as_slice(),
+            b"second-value"
+        );
+        assert!(collected.data.contains_key("SECOND_SECOND_VALUE"));
+        assert_eq!(collected.conflicts[0].resources, vec![0, 2]);
+    }
+
+    #[tokio::test]
+    async fn applies_the_conflict_policy() {
+        let rsecret = conflicting_rsecret(Some(ConflictPolicy::LastWins));
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        assert_eq!(collected.data["plain-key"].0.as_slice(), b"third-value");
+        assert!(check_key_conflicts(&rsecret, &collected.conflicts).is_ok());
+
+        let rsecret = conflicting_rsecret(Some(ConflictPolicy::Error));
+        let collected = collect_secret_data(&test_client(), &rsecret, None).await;
+        let err = check_key_conflicts(&rsecret, &collected.conflicts).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "keys produced by more than one backend: key plain-key produced by Plaintext (0), Plaintext (1), Plaintext (2)"
+        );
+
+        assert!(is_status_outdated(&rsecret, &collected));
+        assert!(!is_status_outdated(
+            &rsecret,
+            &CollectedSecretData::default()
+        ));
+
+        let timed_out = CollectedSecretData {
+            timeouts: vec![1],
+            ..CollectedSecretData::default()
+        };
+        assert!(is_status_outdated(&rsecret, &timed_out));
+        assert_eq!(
+            describe_backend_timeouts(&rsecret, &timed_out.timeouts),
+            "backend Plaintext (1) timed out after 30s"
+        );
+    }
+
     #[test]
     fn parses_hash_id_from_secret_labels() {
         let mut labels = BTreeMap::new();
@@ -285,6 +732,82 @@ mod tests {
         assert_eq!(get_hash_id(&secret), None);
     }
 
+    #[test]
+    fn uses_docker_config_type_for_ecr_auth_token() {
+        let mut rsecret = sample_rsecret();
+        assert_eq!(secret_type(&rsecret), "Opaque");
+
+        rsecret.spec.resources[0].backend = BackendType::ECRAuthToken;
+        assert_eq!(secret_type(&rsecret), "kubernetes.io/dockerconfigjson");
+
+        let opaque = Secret::default();
+        assert!(is_secret_type_outdated(&rsecret, &opaque));
+        let docker_config = Secret {
+            type_: Some("kubernetes.io/dockerconfigjson".into()),
+            ..Secret::default()
+        };
+        assert!(!is_secret_type_outdated(&rsecret, &docker_config));
+    }
+
+    #[test]
+    fn requeues_at_the_earliest_refresh_time() {
+        let now = SystemTime::now();
+        let in_6h = now + Duration::from_secs(6 * 60 * 60);
+        let in_5s = now + Duration::from_secs(5);
+
+        assert_eq!(get_requeue_after(&[], now), DEFAULT_REQUEUE_AFTER);
+        assert_eq!(
+            get_requeue_after(&[Some(in_6h)], now),
+            Duration::from_secs(6 * 60 * 60)
+        );
+        assert_eq!(
+            get_requeue_after(&[Some(in_6h), None], now),
+            DEFAULT_REQUEUE_AFTER
+        );
+        assert_eq!(
+            get_requeue_after(&[Some(in_5s), None], now),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            get_requeue_after(&[Some(now - Duration::from_secs(5))], now),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn matches_referenced_source_objects() {
+        let mut rsecret = sample_rsecret();
+        rsecret.spec.resources[0].backend = BackendType::KubernetesSecret;
+        rsecret.spec.resources[0].data[0].value = "platform/ca-bundle".into();
+
+        assert!(references_source(
+            &rsecret,
+            &BackendType::KubernetesSecret,
+            "platform",
+            "ca-bundle"
+        ));
+        assert!(!references_source(
+            &rsecret,
+            &BackendType::ConfigMap,
+            "platform",
+            "ca-bundle"
+        ));
+        assert!(!references_source(
+            &rsecret,
+            &BackendType::KubernetesSecret,
+            "default",
+            "ca-bundle"
+        ));
+
+        rsecret.spec.resources[0].data[0].value = "local-config".into();
+        assert!(references_source(
+            &rsecret,
+            &BackendType::KubernetesSecret,
+            "default",
+            "local-config"
+        ));
+    }
+
     #[test]
     fn builds_labels_with_hash_and_app() {
         let labels = build_labels("name", 10);
@@ -3,4 +3,6 @@ pub mod aws;
 /// manager
 pub mod manager;
 pub use manager::Manager;
+/// push secret controller
+pub mod push_secret;
 pub mod web;
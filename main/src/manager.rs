@@ -1,11 +1,12 @@
 use chrono::prelude::*;
 use futures::{future::BoxFuture, FutureExt, StreamExt};
-use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
 use kube::{
     api::{Api, ListParams, ResourceExt},
     runtime::{
         controller::{Action, Controller},
-        events::Reporter,
+        events::{Event, EventType, Recorder, Reporter},
+        reflector::{ObjectRef, Store},
     },
     Client, Resource,
 };
@@ -13,10 +14,11 @@ use serde::Serialize;
 use std::sync::Arc;
 use tokio::{sync::RwLock, time::Duration};
 
-use log::{info, warn};
+use log::{error, info, warn};
 
-use crd::RSecret;
+use crd::{BackendType, RSecret, RSecretStatus};
 use k8s::secret;
+use plugins::kubernetes::SOURCE_LABEL;
 use utils::metrics::FAILURES;
 use utils::metrics::RECONCILIATIONS;
 
@@ -40,10 +42,12 @@ async fn reconcile(rsecret: Arc<RSecret>, ctx: Arc<ContextData>) -> Result<Actio
         RSecretAction::Create => {
             secret::add(client.clone(), &name, &ns).await?;
 
-            let data = secret::collect_secret_data(&rs).await;
-            secret::create_k8s_secret(client.clone(), &rs, &data).await?;
+            let Some(collected) = resolve_secret_data(&rs, None, &ctx).await? else {
+                return Ok(Action::requeue(Duration::from_secs(60)));
+            };
+            secret::create_k8s_secret(client.clone(), &rs, &collected.data).await?;
             // ctx.get_ref().metrics.create_counts.inc();
-            Ok(Action::requeue(Duration::from_secs(20)))
+            Ok(Action::requeue(collected.requeue_after))
         }
         RSecretAction::Delete => {
             secret::delete_k8s_secret(client.clone(), &name, &ns).await?;
@@ -58,34 +62,106 @@ async fn reconcile(rsecret: Arc<RSecret>, ctx: Arc<ContextData>) -> Result<Actio
             let k8s_secrets: Api<Secret> = Api::namespaced(client.clone(), &ns);
             let secret = k8s_secrets.get(&name).await;
 
-            match secret {
+            let collected = match secret {
                 Ok(secret) => {
-                    let data = secret::collect_secret_data(&rsecret).await;
-                    let new_hash_id = secret::calculate_secret_hash(&data);
+                    let Some(collected) = resolve_secret_data(&rs, Some(&secret), &ctx).await?
+                    else {
+                        return Ok(Action::requeue(Duration::from_secs(60)));
+                    };
+                    let data = &collected.data;
+                    let new_hash_id = secret::calculate_secret_hash(data);
                     let old_hash_id = secret::get_hash_id(&secret);
 
-                    if old_hash_id == Some(new_hash_id) {
+                    if old_hash_id == Some(new_hash_id)
+                        && !secret::is_secret_type_outdated(&rs, &secret)
+                    {
                         info!("No changes to rsecret {name} in namespace {ns}");
                     } else {
                         info!("Updating rsecret {name} in namespace {ns}");
-                        secret::update_k8s_secret(client.clone(), &rs, &data).await?;
+                        secret::update_k8s_secret(client.clone(), &rs, data).await?;
                         // ctx.get_ref().metrics.update_counts.inc();
                     }
+                    collected
                 }
                 Err(_error) => {
                     // TODO: sort out the error type
                     secret::add(client.clone(), &name, &ns).await?;
 
-                    let data = secret::collect_secret_data(&rsecret).await;
-                    secret::create_k8s_secret(client.clone(), &rs, &data).await?;
+                    let Some(collected) = resolve_secret_data(&rs, None, &ctx).await? else {
+                        return Ok(Action::requeue(Duration::from_secs(60)));
+                    };
+                    secret::create_k8s_secret(client.clone(), &rs, &collected.data).await?;
+                    collected
                 }
-            }
+            };
 
-            Ok(Action::requeue(Duration::from_secs(20)))
+            Ok(Action::requeue(collected.requeue_after))
         }
     }
 }
 
+/// secret data of the rsecret, None when the conflict policy leaves the secret untouched,
+/// the keys produced by more than one backend and the backends that timed out are recorded
+/// in the status and reported with an event
+async fn resolve_secret_data(
+    rsecret: &RSecret,
+    existing: Option<&Secret>,
+    ctx: &ContextData,
+) -> Result<Option<secret::CollectedSecretData>, kube::Error> {
+    let name = rsecret.name_any();
+    let ns = rsecret.namespace().unwrap_or_default();
+    let collected = secret::collect_secret_data(&ctx.client, rsecret, existing).await;
+
+    if secret::is_status_outdated(rsecret, &collected) {
+        let recorded = rsecret.status.clone().unwrap_or_default();
+        let status = RSecretStatus {
+            last_updated: Some(Utc::now()),
+            conflicts: collected.conflicts.clone(),
+            timeouts: collected.timeouts.clone(),
+        };
+        secret::update_status(ctx.client.clone(), &name, &ns, &status).await?;
+
+        if !collected.conflicts.is_empty() && recorded.conflicts != collected.conflicts {
+            let note = secret::describe_key_conflicts(rsecret, &collected.conflicts);
+            publish_warning(rsecret, "KeyConflict", "Merge", note, ctx).await?;
+        }
+        if !collected.timeouts.is_empty() && recorded.timeouts != collected.timeouts {
+            let note = secret::describe_backend_timeouts(rsecret, &collected.timeouts);
+            publish_warning(rsecret, "BackendTimeout", "Resolve", note, ctx).await?;
+        }
+    }
+
+    if let Err(err) = secret::check_key_conflicts(rsecret, &collected.conflicts) {
+        error!("rsecret {name} in namespace {ns} is not written: {err}");
+        FAILURES.inc();
+        return Ok(None);
+    }
+
+    Ok(Some(collected))
+}
+
+/// warning event on the rsecret
+async fn publish_warning(
+    rsecret: &RSecret,
+    reason: &str,
+    action: &str,
+    note: String,
+    ctx: &ContextData,
+) -> Result<(), kube::Error> {
+    // the note of an event is at most 1kB
+    let note: String = note.chars().take(1000).collect();
+    let reporter = ctx.state.read().await.reporter.clone();
+    let recorder = Recorder::new(ctx.client.clone(), reporter);
+    let event = Event {
+        type_: EventType::Warning,
+        reason: reason.into(),
+        note: Some(note),
+        action: action.into(),
+        secondary: None,
+    };
+    recorder.publish(&event, &rsecret.object_ref(&())).await
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum RSecretAction {
     Create,
@@ -108,45 +184,21 @@ fn determine_action(rsecret: &RSecret) -> RSecretAction {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crd::RSecretdSpec;
-    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
-    use kube::core::ObjectMeta;
-
-    fn base_rsecret() -> RSecret {
-        let spec = RSecretdSpec {
-            resources: vec![],
-            description: None,
-        };
-        let mut rsecret = RSecret::new("example", spec);
-        rsecret.metadata = ObjectMeta {
-            namespace: Some("default".into()),
-            ..ObjectMeta::default()
-        };
-        rsecret
-    }
-
-    #[test]
-    fn determine_action_returns_create_when_no_finalizer() {
-        let rsecret = base_rsecret();
-        assert_eq!(determine_action(&rsecret), RSecretAction::Create);
-    }
-
-    #[test]
-    fn determine_action_returns_update_when_finalizer_present() {
-        let mut rsecret = base_rsecret();
-        rsecret.metadata.finalizers = Some(vec!["rsecrets.jerry153fish.com/finalizer".into()]);
-        assert_eq!(determine_action(&rsecret), RSecretAction::Update);
-    }
-
-    #[test]
-    fn determine_action_returns_delete_when_deletion_timestamp_set() {
-        let mut rsecret = base_rsecret();
-        rsecret.metadata.deletion_timestamp = Some(Time("2026-01-01T00:00:00Z".parse().unwrap()));
-        assert_eq!(determine_action(&rsecret), RSecretAction::Delete);
-    }
+/// rsecrets reading the changed secret or config map
+fn rsecrets_referencing<K: Resource>(
+    store: &Store<RSecret>,
+    backend_type: &BackendType,
+    source: &K,
+) -> Vec<ObjectRef<RSecret>> {
+    let namespace = source.namespace().unwrap_or_default();
+    let name = source.name_any();
+
+    store
+        .state()
+        .iter()
+        .filter(|rsecret| secret::references_source(rsecret, backend_type, &namespace, &name))
+        .map(|rsecret| ObjectRef::from_obj(rsecret.as_ref()))
+        .collect()
 }
 
 fn error_policy(_r: Arc<RSecret>, error: &kube::Error, _ctx: Arc<ContextData>) -> Action {
@@ -158,9 +210,9 @@ fn error_policy(_r: Arc<RSecret>, error: &kube::Error, _ctx: Arc<ContextData>) -
 #[derive(Clone)]
 pub struct ContextData {
     /// Kubernetes client to make Kubernetes API requests with. Required for K8S resource management.
-    client: Client,
+    pub(crate) client: Client,
 
-    state: Arc<RwLock<State>>,
+    pub(crate) state: Arc<RwLock<State>>,
 }
 
 /// In-memory reconciler state exposed on /
@@ -213,10 +265,40 @@ impl Manager {
         );
 
         // All good. Start controller and return its future.
-        let drainer = Controller::new(rsecrets.clone(), kube::runtime::watcher::Config::default())
-            .run(reconcile, error_policy, context)
+        let controller =
+            Controller::new(rsecrets.clone(), kube::runtime::watcher::Config::default());
+        let secret_store = controller.store();
+        let config_map_store = controller.store();
+
+        // Re-trigger the rsecrets reading a labelled secret or config map when the source changes,
+        // and the rsecrets whose watched backend keys changed
+        let sources =
+            kube::runtime::watcher::Config::default().labels(&format!("{SOURCE_LABEL}=true"));
+        let reconcile_requests = plugins::registry::reconcile_requests()
+            .map(|(namespace, name)| ObjectRef::new(&name).within(&namespace));
+        let rsecret_drainer = controller
+            .reconcile_on(reconcile_requests)
+            .watches(
+                Api::<Secret>::all(client.clone()),
+                sources.clone(),
+                move |source| {
+                    rsecrets_referencing(&secret_store, &BackendType::KubernetesSecret, &source)
+                },
+            )
+            .watches(
+                Api::<ConfigMap>::all(client.clone()),
+                sources,
+                move |source| {
+                    rsecrets_referencing(&config_map_store, &BackendType::ConfigMap, &source)
+                },
+            )
+            .run(reconcile, error_policy, context.clone())
             .filter_map(|x| async move { std::result::Result::ok(x) })
-            .for_each(|_| futures::future::ready(()))
+            .for_each(|_| futures::future::ready(()));
+
+        let push_secret_drainer = crate::push_secret::run(client.clone(), context).await;
+        let drainer = futures::future::join(rsecret_drainer, push_secret_drainer)
+            .map(|_| ())
             .boxed();
 
         (Self { state }, drainer)
@@ -227,3 +309,45 @@ impl Manager {
         self.state.read().await.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crd::RSecretdSpec;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use kube::core::ObjectMeta;
+
+    fn base_rsecret() -> RSecret {
+        let spec = RSecretdSpec {
+            resources: vec![],
+            description: None,
+            conflict_policy: None,
+        };
+        let mut rsecret = RSecret::new("example", spec);
+        rsecret.metadata = ObjectMeta {
+            namespace: Some("default".into()),
+            ..ObjectMeta::default()
+        };
+        rsecret
+    }
+
+    #[test]
+    fn determine_action_returns_create_when_no_finalizer() {
+        let rsecret = base_rsecret();
+        assert_eq!(determine_action(&rsecret), RSecretAction::Create);
+    }
+
+    #[test]
+    fn determine_action_returns_update_when_finalizer_present() {
+        let mut rsecret = base_rsecret();
+        rsecret.metadata.finalizers = Some(vec!["rsecrets.jerry153fish.com/finalizer".into()]);
+        assert_eq!(determine_action(&rsecret), RSecretAction::Update);
+    }
+
+    #[test]
+    fn determine_action_returns_delete_when_deletion_timestamp_set() {
+        let mut rsecret = base_rsecret();
+        rsecret.metadata.deletion_timestamp = Some(Time("2026-01-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(determine_action(&rsecret), RSecretAction::Delete);
+    }
+}
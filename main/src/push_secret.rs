@@ -0,0 +1,199 @@
+use chrono::prelude::*;
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    api::{Api, ListParams, PartialObjectMeta, ResourceExt},
+    runtime::{
+        controller::{Action, Controller},
+        reflector::{ObjectRef, Store},
+    },
+    Client, Resource,
+};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use log::{error, info, warn};
+
+use crd::{PushSecret, PushSecretStatus};
+use k8s::push_secret;
+use utils::metrics::FAILURES;
+use utils::metrics::RECONCILIATIONS;
+
+use crate::manager::ContextData;
+
+async fn reconcile(
+    push_secret: Arc<PushSecret>,
+    ctx: Arc<ContextData>,
+) -> Result<Action, kube::Error> {
+    RECONCILIATIONS.inc();
+
+    let client = ctx.client.clone();
+    ctx.state.write().await.last_event = Utc::now();
+    let name = push_secret.name_any();
+    let ns = push_secret.namespace().expect("push secret is namespaced");
+
+    match determine_action(&push_secret) {
+        PushSecretAction::Create => {
+            push_secret::add(client.clone(), &name, &ns).await?;
+            Ok(Action::requeue(Duration::from_secs(1)))
+        }
+        PushSecretAction::Delete => {
+            if let Err(err) = push_secret::delete_remote_data(&push_secret).await {
+                error!("failed to delete the remote values of push secret {name} in namespace {ns}: {err}");
+                FAILURES.inc();
+                return Ok(Action::requeue(Duration::from_secs(60)));
+            }
+
+            push_secret::delete(client.clone(), &name, &ns).await?;
+            Ok(Action::await_change())
+        }
+        PushSecretAction::Push => {
+            let k8s_secrets: Api<Secret> = Api::namespaced(client.clone(), &ns);
+            let secret = k8s_secrets.get(&push_secret.spec.secret_name).await?;
+
+            let data = match push_secret::collect_push_data(&push_secret, &secret) {
+                Ok(data) => data,
+                Err(err) => {
+                    error!("{err}");
+                    FAILURES.inc();
+                    return Ok(Action::requeue(Duration::from_secs(60)));
+                }
+            };
+
+            let hash_id = push_secret::calculate_push_hash(&push_secret.spec.backend, &data);
+            if !push_secret::is_push_needed(&push_secret, hash_id) {
+                info!("No changes to push secret {name} in namespace {ns}");
+                return Ok(Action::requeue(Duration::from_secs(5 * 60)));
+            }
+
+            info!("Pushing push secret {name} in namespace {ns}");
+            if let Err(err) = push_secret::push_data(&push_secret.spec.backend, &data).await {
+                error!("failed to push push secret {name} in namespace {ns}: {err}");
+                FAILURES.inc();
+                return Ok(Action::requeue(Duration::from_secs(60)));
+            }
+
+            if push_secret::is_delete_policy(&push_secret) {
+                let stale_paths = push_secret::find_stale_paths(&push_secret, &data);
+                if let Err(err) = push_secret::delete_remote_paths(&stale_paths).await {
+                    error!("failed to delete the remote values no longer pushed by push secret {name} in namespace {ns}: {err}");
+                    FAILURES.inc();
+                    return Ok(Action::requeue(Duration::from_secs(60)));
+                }
+            }
+
+            let status = PushSecretStatus {
+                last_updated: Some(Utc::now()),
+                hash_id: Some(hash_id.to_string()),
+                remote_paths: Some(data.into_keys().collect()),
+                backend: Some(push_secret.spec.backend.clone()),
+            };
+            push_secret::update_status(client.clone(), &name, &ns, &status).await?;
+
+            Ok(Action::requeue(Duration::from_secs(5 * 60)))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PushSecretAction {
+    Create,
+    Delete,
+    Push,
+}
+
+fn determine_action(push_secret: &PushSecret) -> PushSecretAction {
+    if push_secret.meta().deletion_timestamp.is_some() {
+        PushSecretAction::Delete
+    } else if push_secret
+        .meta()
+        .finalizers
+        .as_ref()
+        .is_none_or(|finalizers| finalizers.is_empty())
+    {
+        PushSecretAction::Create
+    } else {
+        PushSecretAction::Push
+    }
+}
+
+/// push secrets pushing the keys of the changed secret
+fn push_secrets_referencing(
+    store: &Store<PushSecret>,
+    secret: &PartialObjectMeta<Secret>,
+) -> Vec<ObjectRef<PushSecret>> {
+    let namespace = secret.namespace().unwrap_or_default();
+    let name = secret.name_any();
+
+    store
+        .state()
+        .iter()
+        .filter(|push_secret| push_secret::references_secret(push_secret, &namespace, &name))
+        .map(|push_secret| ObjectRef::from_obj(push_secret.as_ref()))
+        .collect()
+}
+
+fn error_policy(_p: Arc<PushSecret>, error: &kube::Error, _ctx: Arc<ContextData>) -> Action {
+    warn!("push secret reconcile failed: {error:?}");
+    FAILURES.inc();
+    Action::requeue(Duration::from_secs(5 * 60))
+}
+
+/// controller of the push secrets, re-triggered when the pushed secret changes
+/// skipped when the push secret crd is not installed, the rsecrets are still reconciled
+pub async fn run(client: Client, context: Arc<ContextData>) -> BoxFuture<'static, ()> {
+    let push_secrets: Api<PushSecret> = Api::all(client.clone());
+
+    // Ensure CRD is installed before loop-watching
+    if let Err(err) = push_secrets.list(&ListParams::default().limit(1)).await {
+        error!(
+            "push secrets are not reconciled, is the crd installed? please run: cargo run --bin crd | kubectl apply -f - ({err})"
+        );
+        return futures::future::ready(()).boxed();
+    }
+
+    let controller = Controller::new(push_secrets, kube::runtime::watcher::Config::default());
+    let store = controller.store();
+
+    // only the metadata of the secrets is watched, their values are read when a push secret references them
+    controller
+        .watches(
+            Api::<PartialObjectMeta<Secret>>::all(client),
+            kube::runtime::watcher::Config::default(),
+            move |secret| push_secrets_referencing(&store, &secret),
+        )
+        .run(reconcile, error_policy, context)
+        .filter_map(|x| async move { std::result::Result::ok(x) })
+        .for_each(|_| futures::future::ready(()))
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crd::{PushBackendType, PushSecretSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+
+    fn base_push_secret() -> PushSecret {
+        let spec = PushSecretSpec {
+            secret_name: "db-tls".into(),
+            backend: PushBackendType::SSM,
+            data: vec![],
+            deletion_policy: None,
+        };
+        PushSecret::new("example", spec)
+    }
+
+    #[test]
+    fn determine_action_follows_the_finalizer() {
+        let mut push_secret = base_push_secret();
+        assert_eq!(determine_action(&push_secret), PushSecretAction::Create);
+
+        push_secret.metadata.finalizers = Some(vec![push_secret::PUSH_SECRET_FINALIZER.into()]);
+        assert_eq!(determine_action(&push_secret), PushSecretAction::Push);
+
+        push_secret.metadata.deletion_timestamp =
+            Some(Time("2026-01-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(determine_action(&push_secret), PushSecretAction::Delete);
+    }
+}
@@ -0,0 +1,19 @@
+//! Reference provider of the external backend, answering like the plaintext backend.
+//!
+//! cargo run -p plugins --example reference_provider -- /var/run/remote-secrets/providers/reference.sock
+
+use plugins::external::{serve, ReferenceProvider};
+use std::sync::Arc;
+use tokio::net::UnixListener;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let socket = std::env::args()
+        .nth(1)
+        .unwrap_or("/var/run/remote-secrets/providers/reference.sock".to_owned());
+    let _ = std::fs::remove_file(&socket);
+
+    let listener = UnixListener::bind(&socket)?;
+    println!("serving the reference provider on {socket}");
+    serve(listener, Arc::new(ReferenceProvider)).await
+}
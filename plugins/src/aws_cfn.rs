@@ -1,18 +1,57 @@
-use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config, is_test_env};
+use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config_for_region, is_test_env};
+use crate::concurrent::resolve_entries_data;
+use crate::registry::Provider;
 use async_trait::async_trait;
+use aws_sdk_cloudformation::types::{Output, Stack};
 use cached::macros::cached;
-use crd::{Backend, RemoteValue, SecretData};
+use crd::{Backend, CloudformationSource, EntryConflict, RemoteData, RemoteValue, SecretData};
 
 use anyhow::{anyhow, Result};
 use k8s_openapi::ByteString;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use utils::value::get_secret_data;
 
+const NESTED_STACK_RESOURCE_TYPE: &str = "AWS::CloudFormation::Stack";
+
 pub struct Cloudformation {
     data: Vec<SecretData>,
 }
 
+/// where the outputs of a cloudformation secret data are read from
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CloudformationQuery {
+    /// stack name, export name or tag selector depending on the source
+    pub value: String,
+    pub source: CloudformationSource,
+    pub region: Option<String>,
+    pub nested_stacks: bool,
+}
+
+impl CloudformationQuery {
+    /// query the outputs of a single stack in the default region
+    pub fn stack(stack_name: &str) -> CloudformationQuery {
+        CloudformationQuery {
+            value: stack_name.to_owned(),
+            source: CloudformationSource::Stack,
+            region: None,
+            nested_stacks: false,
+        }
+    }
+
+    pub fn from_secret_data(secret_data: &SecretData) -> CloudformationQuery {
+        CloudformationQuery {
+            value: secret_data.value.clone(),
+            source: secret_data
+                .cloudformation_source
+                .clone()
+                .unwrap_or(CloudformationSource::Stack),
+            region: secret_data.region.clone(),
+            nested_stacks: secret_data.nested_stacks.unwrap_or_default(),
+        }
+    }
+}
+
 #[async_trait]
 impl RemoteValue for Cloudformation {
     fn from_backend(backend: &Backend) -> Cloudformation {
@@ -21,55 +60,71 @@ impl RemoteValue for Cloudformation {
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries_data(&self.data, |secret_data| async move {
+            let query = CloudformationQuery::from_secret_data(secret_data);
+            // an export only has one value, which is keyed by the export name
+            let remote_path = match query.source {
+                CloudformationSource::Export => secret_data
+                    .remote_path
+                    .clone()
+                    .or_else(|| Some(query.value.clone())),
+                _ => secret_data.remote_path.clone(),
+            };
+
+            let outputs = match get_cloudformation_outputs(query).await {
+                Ok(outputs) => outputs,
+                Err(err) => {
+                    log::error!("{}", err);
+                    return RemoteData::default();
+                }
+            };
+            // several stacks, or nested stacks, may have outputs named alike, the first one is kept
+            let duplicates = find_duplicate_outputs(&outputs);
 
-        for secret_data in self.data.iter() {
             // specific the output value for 1-1 mapping k8s secret key
             // TODO: support the output value is not dict
-            if secret_data.key.is_some() && secret_data.remote_path.is_some() {
-                let cloudformation_secret_data = get_cloudformation_output(
-                    secret_data.value.clone(),
-                    secret_data.remote_path.clone().unwrap(),
-                )
-                .await;
-
-                match cloudformation_secret_data {
-                    Ok(cloudformation_secret_data) => {
-                        let data = get_secret_data(secret_data, &cloudformation_secret_data);
-
-                        secrets = data
-                            .into_iter()
-                            .chain(secrets.clone().into_iter())
-                            .collect();
-                    }
+            if let (Some(key), Some(remote_path)) = (&secret_data.key, remote_path) {
+                let data = match find_output(&outputs, &remote_path) {
+                    Ok(value) => get_secret_data(secret_data, &value),
                     Err(err) => {
                         log::error!("{}", err);
+                        BTreeMap::new()
                     }
-                }
+                };
+                let conflicts = match duplicates.contains(&remote_path) {
+                    true => vec![EntryConflict {
+                        key: key.to_owned(),
+                        entries: vec![],
+                    }],
+                    false => vec![],
+                };
+                RemoteData { data, conflicts }
             } else {
                 // insert the whole cloudformation outputs into k8s secret data
-                let cloudformation_secret_data =
-                    get_cloudformation_outputs_as_secret_data(secret_data.value.clone()).await;
-
-                match cloudformation_secret_data {
-                    Ok(cloudformation_secret_data) => {
-                        secrets = cloudformation_secret_data
-                            .into_iter()
-                            .chain(secrets.clone().into_iter())
-                            .collect();
-                    }
-                    Err(err) => {
-                        log::error!("{}", err);
-                    }
+                RemoteData {
+                    data: outputs_to_secret_data(&outputs),
+                    conflicts: duplicates
+                        .into_iter()
+                        .map(|key| EntryConflict {
+                            key,
+                            entries: vec![],
+                        })
+                        .collect(),
                 }
             }
-        }
-
-        secrets
+        })
+        .await
     }
 }
 
+/// registers the cloudformation backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Cloudformation", |backend, _| {
+        Box::new(Cloudformation::from_backend(backend))
+    }));
+}
+
 /// get the cloudformation client
 pub fn cloudformation_client(conf: &aws_types::SdkConfig) -> aws_sdk_cloudformation::Client {
     let mut cloudformation_config_builder = aws_sdk_cloudformation::config::Builder::from(conf);
@@ -84,32 +139,241 @@ pub fn cloudformation_client(conf: &aws_types::SdkConfig) -> aws_sdk_cloudformat
     aws_sdk_cloudformation::Client::from_conf(cloudformation_config_builder.build())
 }
 
-/// get the data from the secret manager store by name
+/// get the outputs of the stacks or exports described by the query
 /// Will cache the result for 60s
 #[cached(ttl = 60)]
-pub async fn get_cloudformation_outputs(
-    stack_name: String,
-) -> Result<Vec<aws_sdk_cloudformation::types::Output>> {
-    let shared_config = get_aws_sdk_config().await?;
+pub async fn get_cloudformation_outputs(query: CloudformationQuery) -> Result<Vec<Output>> {
+    let shared_config = get_aws_sdk_config_for_region(query.region.clone()).await?;
     let client = cloudformation_client(&shared_config);
+
+    let stacks = match query.source {
+        CloudformationSource::Export => {
+            return get_cloudformation_export(&client, &query.value).await;
+        }
+        CloudformationSource::Stack => vec![describe_stack(&client, &query.value).await?],
+        CloudformationSource::Tags => {
+            let selector = parse_tag_selector(&query.value)?;
+            let stacks = describe_stacks_by_tags(&client, &selector).await?;
+            if stacks.is_empty() {
+                return Err(anyhow!("no stack found with tags {}", query.value));
+            }
+            stacks
+        }
+    };
+
+    let mut outputs = Vec::new();
+    for stack in stacks.iter() {
+        outputs.extend(stack.outputs().iter().cloned());
+        if query.nested_stacks {
+            outputs.extend(get_nested_stack_outputs(&client, stack).await?);
+        }
+    }
+
+    Ok(outputs)
+}
+
+/// describe a single stack by name or id
+async fn describe_stack(
+    client: &aws_sdk_cloudformation::Client,
+    stack_name: &str,
+) -> Result<Stack> {
     let resp = client
         .describe_stacks()
         .stack_name(stack_name)
         .send()
         .await?;
 
-    let result = resp
+    let stack = resp
         .stacks()
         .first()
-        .ok_or_else(|| anyhow!("no first stack found"))?
-        .outputs();
+        .ok_or_else(|| anyhow!("no first stack found"))?;
+
+    Ok(stack.to_owned())
+}
+
+/// describe all the stacks whose tags contain every pair of the selector, ordered by stack name
+async fn describe_stacks_by_tags(
+    client: &aws_sdk_cloudformation::Client,
+    selector: &BTreeMap<String, String>,
+) -> Result<Vec<Stack>> {
+    let mut stacks = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let resp = client
+            .describe_stacks()
+            .set_next_token(next_token)
+            .send()
+            .await?;
+
+        stacks.extend(
+            resp.stacks()
+                .iter()
+                .filter(|stack| stack_matches_tags(stack, selector))
+                .cloned(),
+        );
+
+        next_token = resp.next_token().map(|token| token.to_owned());
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    stacks.sort_by(|a, b| a.stack_name().cmp(&b.stack_name()));
+    Ok(stacks)
+}
+
+/// get the value of an export as a single output keyed by the export name
+async fn get_cloudformation_export(
+    client: &aws_sdk_cloudformation::Client,
+    export_name: &str,
+) -> Result<Vec<Output>> {
+    let mut next_token = None;
+
+    loop {
+        let resp = client
+            .list_exports()
+            .set_next_token(next_token)
+            .send()
+            .await?;
+
+        if let Some(export) = resp
+            .exports()
+            .iter()
+            .find(|export| export.name() == Some(export_name))
+        {
+            let output = Output::builder()
+                .output_key(export_name)
+                .output_value(export.value().unwrap_or_default())
+                .build();
+            return Ok(vec![output]);
+        }
+
+        next_token = resp.next_token().map(|token| token.to_owned());
+        if next_token.is_none() {
+            return Err(anyhow!("no export found with name {export_name}"));
+        }
+    }
+}
+
+/// get the outputs of all the nested stacks under the stack
+/// keys are prefixed with the logical ids of the nested stacks eg Network.VpcId
+async fn get_nested_stack_outputs(
+    client: &aws_sdk_cloudformation::Client,
+    stack: &Stack,
+) -> Result<Vec<Output>> {
+    let mut outputs = Vec::new();
+    let root_id = stack
+        .stack_id()
+        .or(stack.stack_name())
+        .ok_or_else(|| anyhow!("stack has no id"))?;
+    let mut pending = vec![(String::new(), root_id.to_owned())];
+
+    while let Some((prefix, stack_id)) = pending.pop() {
+        for (logical_id, physical_id) in list_nested_stacks(client, &stack_id).await? {
+            let nested_prefix = nested_output_prefix(&prefix, &logical_id);
+            let nested = describe_stack(client, &physical_id).await?;
+            outputs.extend(
+                nested
+                    .outputs()
+                    .iter()
+                    .map(|output| prefixed_output(&nested_prefix, output)),
+            );
+            pending.push((nested_prefix, physical_id));
+        }
+    }
+
+    Ok(outputs)
+}
 
-    Ok(result.to_owned())
+/// list the logical and physical ids of the nested stacks directly under the stack
+async fn list_nested_stacks(
+    client: &aws_sdk_cloudformation::Client,
+    stack_id: &str,
+) -> Result<Vec<(String, String)>> {
+    let mut nested_stacks = Vec::new();
+    let mut next_token = None;
+
+    loop {
+        let resp = client
+            .list_stack_resources()
+            .stack_name(stack_id)
+            .set_next_token(next_token)
+            .send()
+            .await?;
+
+        for resource in resp.stack_resource_summaries() {
+            if resource.resource_type() != Some(NESTED_STACK_RESOURCE_TYPE) {
+                continue;
+            }
+            if let (Some(logical_id), Some(physical_id)) = (
+                resource.logical_resource_id(),
+                resource.physical_resource_id(),
+            ) {
+                nested_stacks.push((logical_id.to_owned(), physical_id.to_owned()));
+            }
+        }
+
+        next_token = resp.next_token().map(|token| token.to_owned());
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(nested_stacks)
+}
+
+/// parse a stack tag selector eg env=prod,team=data
+pub fn parse_tag_selector(selector: &str) -> Result<BTreeMap<String, String>> {
+    let mut tags = BTreeMap::new();
+    for pair in selector.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid stack tag selector {pair}, expected key=value"))?;
+        tags.insert(key.trim().to_owned(), value.trim().to_owned());
+    }
+
+    if tags.is_empty() {
+        return Err(anyhow!("empty stack tag selector"));
+    }
+
+    Ok(tags)
+}
+
+fn stack_matches_tags(stack: &Stack, selector: &BTreeMap<String, String>) -> bool {
+    selector.iter().all(|(key, value)| {
+        stack
+            .tags()
+            .iter()
+            .any(|tag| tag.key() == Some(key.as_str()) && tag.value() == Some(value.as_str()))
+    })
+}
+
+fn nested_output_prefix(prefix: &str, logical_id: &str) -> String {
+    format!("{prefix}{logical_id}.")
+}
+
+fn prefixed_output(prefix: &str, output: &Output) -> Output {
+    Output::builder()
+        .output_key(format!(
+            "{prefix}{}",
+            output.output_key().unwrap_or_default()
+        ))
+        .output_value(output.output_value().unwrap_or_default())
+        .build()
 }
 
 /// get the output value from the cloudformation stack
-pub async fn get_cloudformation_output(stack_name: String, remote_path: String) -> Result<String> {
-    let outputs = get_cloudformation_outputs(stack_name).await?;
+pub async fn get_cloudformation_output(
+    query: CloudformationQuery,
+    remote_path: String,
+) -> Result<String> {
+    let outputs = get_cloudformation_outputs(query).await?;
+    find_output(&outputs, &remote_path)
+}
+
+/// the value of the first output named remote_path
+fn find_output(outputs: &[Output], remote_path: &str) -> Result<String> {
     let result = outputs
         .iter()
         .find(|output| output.output_key().unwrap_or_default() == remote_path)
@@ -122,23 +386,39 @@ pub async fn get_cloudformation_output(stack_name: String, remote_path: String)
 
 // get the secret data from the whole outputs of the cloudformation stack
 pub async fn get_cloudformation_outputs_as_secret_data(
-    stack_name: String,
+    query: CloudformationQuery,
 ) -> Result<BTreeMap<String, ByteString>> {
-    let outputs = get_cloudformation_outputs(stack_name).await?;
+    let outputs = get_cloudformation_outputs(query).await?;
+    Ok(outputs_to_secret_data(&outputs))
+}
+
+/// every output keyed by its name, the first output of a name is kept
+pub fn outputs_to_secret_data(outputs: &[Output]) -> BTreeMap<String, ByteString> {
     let mut secrets = BTreeMap::new();
     for output in outputs {
         let remote_path = output.output_key().unwrap_or_default().to_owned();
-        let output_value = ByteString(
-            output
-                .output_value()
-                .unwrap_or_default()
-                .as_bytes()
-                .to_vec(),
-        );
-        secrets.insert(remote_path, output_value);
+        secrets.entry(remote_path).or_insert_with(|| {
+            ByteString(
+                output
+                    .output_value()
+                    .unwrap_or_default()
+                    .as_bytes()
+                    .to_vec(),
+            )
+        });
     }
 
-    Ok(secrets)
+    secrets
+}
+
+/// names of the outputs found more than once, in several stacks or nested stacks
+pub fn find_duplicate_outputs(outputs: &[Output]) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    outputs
+        .iter()
+        .map(|output| output.output_key().unwrap_or_default().to_owned())
+        .filter(|key| !seen.insert(key.clone()))
+        .collect()
 }
 
 #[cfg(test)]
@@ -161,9 +441,12 @@ mod tests {
             return;
         }
 
-        let result = get_cloudformation_output("MyTestStack".to_string(), "S3Bucket".to_string())
-            .await
-            .unwrap();
+        let result = get_cloudformation_output(
+            CloudformationQuery::stack("MyTestStack"),
+            "S3Bucket".to_string(),
+        )
+        .await
+        .unwrap();
 
         assert_eq!(result, "S3Bucket");
     }
@@ -174,15 +457,111 @@ mod tests {
             return;
         }
 
-        let result = get_cloudformation_outputs_as_secret_data("MyTestStack".to_string())
-            .await
-            .unwrap();
+        let result =
+            get_cloudformation_outputs_as_secret_data(CloudformationQuery::stack("MyTestStack"))
+                .await
+                .unwrap();
 
         let data_string = serde_json::to_string(&result).unwrap();
 
         assert!(data_string.contains("UzNCdWNrZXQ"));
     }
 
+    #[test]
+    fn test_parse_tag_selector() {
+        let tags = parse_tag_selector("env=prod, team=data").unwrap();
+
+        assert_eq!(tags.get("env"), Some(&"prod".to_string()));
+        assert_eq!(tags.get("team"), Some(&"data".to_string()));
+        assert!(parse_tag_selector("env").is_err());
+        assert!(parse_tag_selector(" , ").is_err());
+    }
+
+    #[test]
+    fn test_stack_matches_tags() {
+        let tag = |key: &str, value: &str| {
+            aws_sdk_cloudformation::types::Tag::builder()
+                .key(key)
+                .value(value)
+                .build()
+        };
+        let stack = Stack::builder()
+            .stack_name("MyTestStack")
+            .tags(tag("env", "prod"))
+            .tags(tag("team", "data"))
+            .build();
+
+        assert!(stack_matches_tags(
+            &stack,
+            &parse_tag_selector("env=prod").unwrap()
+        ));
+        assert!(stack_matches_tags(
+            &stack,
+            &parse_tag_selector("env=prod,team=data").unwrap()
+        ));
+        assert!(!stack_matches_tags(
+            &stack,
+            &parse_tag_selector("env=dev").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_prefixed_nested_output() {
+        let output = Output::builder()
+            .output_key("VpcId")
+            .output_value("vpc-1")
+            .build();
+        let prefix = nested_output_prefix(&nested_output_prefix("", "Network"), "Vpc");
+        let nested = prefixed_output(&prefix, &output);
+
+        assert_eq!(nested.output_key(), Some("Network.Vpc.VpcId"));
+        assert_eq!(nested.output_value(), Some("vpc-1"));
+    }
+
+    #[test]
+    fn test_duplicate_outputs() {
+        let output = |key: &str, value: &str| {
+            Output::builder()
+                .output_key(key)
+                .output_value(value)
+                .build()
+        };
+        // two stacks selected by their tags with a BucketName output each
+        let outputs = vec![
+            output("BucketName", "first-bucket"),
+            output("QueueUrl", "queue"),
+            output("BucketName", "second-bucket"),
+        ];
+
+        let secrets = outputs_to_secret_data(&outputs);
+        assert_eq!(secrets.len(), 2);
+        assert_eq!(secrets["BucketName"].0, b"first-bucket");
+        assert_eq!(
+            find_duplicate_outputs(&outputs),
+            BTreeSet::from(["BucketName".to_string()])
+        );
+        assert_eq!(find_output(&outputs, "BucketName").unwrap(), "first-bucket");
+    }
+
+    #[tokio::test]
+    async fn test_get_cloudformation_export() {
+        if skip_without_mock_env() {
+            return;
+        }
+
+        let query = CloudformationQuery {
+            value: "MyTestStack-S3Bucket".to_string(),
+            source: CloudformationSource::Export,
+            region: None,
+            nested_stacks: false,
+        };
+        let result = get_cloudformation_output(query, "MyTestStack-S3Bucket".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(result, "S3Bucket");
+    }
+
     #[tokio::test]
     async fn test_cloudformation() {
         if skip_without_mock_env() {
@@ -17,6 +17,30 @@ pub fn aws_endpoint_url() -> &'static str {
     Box::leak(url.into_boxed_str())
 }
 
+#[cfg(feature = "aws")]
 pub async fn get_aws_sdk_config() -> Result<aws_types::SdkConfig> {
-    Ok(aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await)
+    get_aws_sdk_config_for_region(None).await
+}
+
+/// Get the shared AWS config, optionally overriding the region of the provider chain.
+#[cfg(feature = "aws")]
+pub async fn get_aws_sdk_config_for_region(region: Option<String>) -> Result<aws_types::SdkConfig> {
+    let loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    let loader = match region {
+        Some(region) => loader.region(aws_types::region::Region::new(region)),
+        None => loader,
+    };
+    Ok(loader.load().await)
+}
+
+/// read the object of the s3://bucket/key url, for the state files of terraform and pulumi
+#[cfg(feature = "aws")]
+pub async fn read_s3_url(url: &str) -> Result<Vec<u8>> {
+    let (bucket, key) = crate::aws_s3::parse_s3_url(url)?;
+    crate::aws_s3::get_s3_object(bucket, key, None, None).await
+}
+
+#[cfg(not(feature = "aws"))]
+pub async fn read_s3_url(url: &str) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!("reading {url} requires the aws feature"))
 }
@@ -0,0 +1,344 @@
+use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config_for_region, is_test_env};
+use crate::concurrent::{limit_request, reject_decoding};
+use crate::registry::Provider;
+use async_trait::async_trait;
+use crd::{Backend, EntryConflict, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use k8s_openapi::ByteString;
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// secret key of the rendered docker config
+pub const DOCKER_CONFIG_JSON_KEY: &str = ".dockerconfigjson";
+/// tokens are refreshed at this fraction of their validity, ie 6h for the 12h ecr tokens
+const ECR_TOKEN_REFRESH_RATIO: u32 = 2;
+
+lazy_static! {
+    /// authorization tokens by requested region, kept until their refresh time
+    static ref ECR_TOKENS: Mutex<BTreeMap<Option<String>, EcrToken>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Clone, Debug)]
+pub struct EcrToken {
+    pub username: String,
+    pub password: String,
+    /// region the token was issued in
+    pub region: String,
+    pub refresh_at: SystemTime,
+}
+
+#[derive(Clone, Debug)]
+pub struct ECRAuthToken {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for ECRAuthToken {
+    fn from_backend(backend: &Backend) -> ECRAuthToken {
+        ECRAuthToken {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let mut secrets = BTreeMap::new();
+        let mut auths = BTreeMap::new();
+        let mut entries_by_host: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        for (index, secret_data) in self.data.iter().enumerate() {
+            if let Err(err) = reject_decoding("ECRAuthToken", secret_data) {
+                log::error!("{err}");
+                continue;
+            }
+            let (host, _) = parse_ecr_registry(&secret_data.value);
+
+            match limit_request(get_ecr_authorization_token(token_region(secret_data))).await {
+                Ok(token) => {
+                    let host = host.unwrap_or_else(|| {
+                        format!(
+                            "{}.dkr.ecr.{}.amazonaws.com",
+                            secret_data.value, token.region
+                        )
+                    });
+                    entries_by_host.entry(host.clone()).or_default().push(index);
+                    // the first entry of a registry wins, like the keys of the other backends
+                    auths.entry(host).or_insert(token);
+                }
+                Err(err) => {
+                    log::error!("{}", err);
+                }
+            }
+        }
+
+        if !auths.is_empty() {
+            let docker_config = render_docker_config(&auths);
+            secrets.insert(
+                DOCKER_CONFIG_JSON_KEY.to_owned(),
+                ByteString(docker_config.to_string().into_bytes()),
+            );
+        }
+
+        // entries of the same registry are reported on the docker config holding its auth
+        let conflicts = entries_by_host
+            .into_values()
+            .filter(|entries| entries.len() > 1)
+            .map(|entries| EntryConflict {
+                key: DOCKER_CONFIG_JSON_KEY.to_owned(),
+                entries,
+            })
+            .collect();
+
+        RemoteData {
+            data: secrets,
+            conflicts,
+        }
+    }
+
+    /// the earliest refresh time of the tokens, None when a token is missing so it is retried
+    fn refresh_at(&self) -> Option<SystemTime> {
+        let tokens = ECR_TOKENS.lock().unwrap();
+        self.data
+            .iter()
+            .map(|secret_data| {
+                tokens
+                    .get(&token_region(secret_data))
+                    .map(|token| token.refresh_at)
+            })
+            .collect::<Option<Vec<SystemTime>>>()?
+            .into_iter()
+            .min()
+    }
+}
+
+/// region the token of the registry is requested in, None for the default region
+fn token_region(secret_data: &SecretData) -> Option<String> {
+    let (_, host_region) = parse_ecr_registry(&secret_data.value);
+    secret_data.region.clone().or(host_region)
+}
+
+/// registers the ecr authorization token backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("ECRAuthToken", |backend, _| {
+        Box::new(ECRAuthToken::from_backend(backend))
+    }));
+}
+
+/// get the ecr client
+pub fn ecr_client(conf: &aws_types::SdkConfig) -> aws_sdk_ecr::Client {
+    let mut ecr_config_builder = aws_sdk_ecr::config::Builder::from(conf);
+    if is_test_env() {
+        log::info!("Using local AWS endpoint for ECR {}", aws_endpoint_url());
+        ecr_config_builder = ecr_config_builder.endpoint_url(aws_endpoint_url())
+    }
+    aws_sdk_ecr::Client::from_conf(ecr_config_builder.build())
+}
+
+/// registry is either an account id or a registry host eg 123456789012.dkr.ecr.us-east-1.amazonaws.com
+/// returns the host if given and the region in the host
+pub fn parse_ecr_registry(registry: &str) -> (Option<String>, Option<String>) {
+    if registry.chars().all(|c| c.is_ascii_digit()) {
+        return (None, None);
+    }
+
+    let region = registry
+        .split('.')
+        .collect::<Vec<_>>()
+        .get(1..4)
+        .and_then(|parts| match parts {
+            ["dkr", "ecr", region] => Some(region.to_string()),
+            _ => None,
+        });
+
+    (Some(registry.to_owned()), region)
+}
+
+/// get an authorization token valid for every registry the controller can access in the region
+/// the token is reused until halfway through its validity, well before it expires
+pub async fn get_ecr_authorization_token(region: Option<String>) -> Result<EcrToken> {
+    if let Some(token) = ECR_TOKENS.lock().unwrap().get(&region) {
+        if SystemTime::now() < token.refresh_at {
+            return Ok(token.clone());
+        }
+    }
+
+    let shared_config = get_aws_sdk_config_for_region(region.clone()).await?;
+    let resolved_region = shared_config
+        .region()
+        .map(|region| region.to_string())
+        .ok_or_else(|| anyhow!("no region configured for ECR"))?;
+    let client = ecr_client(&shared_config);
+    let output = client.get_authorization_token().send().await?;
+
+    let data = output
+        .authorization_data()
+        .first()
+        .ok_or_else(|| anyhow!("no authorization data found"))?;
+    let decoded = BASE64.decode(data.authorization_token().unwrap_or_default())?;
+    let (username, password) = String::from_utf8(decoded)?
+        .split_once(':')
+        .map(|(username, password)| (username.to_owned(), password.to_owned()))
+        .ok_or_else(|| anyhow!("invalid ECR authorization token"))?;
+
+    let expires_at = data
+        .expires_at()
+        .and_then(|expires_at| SystemTime::try_from(*expires_at).ok())
+        .unwrap_or_else(SystemTime::now);
+
+    let token = EcrToken {
+        username,
+        password,
+        region: resolved_region,
+        refresh_at: refresh_time(SystemTime::now(), expires_at),
+    };
+
+    ECR_TOKENS.lock().unwrap().insert(region, token.clone());
+
+    Ok(token)
+}
+
+/// when to refresh a token issued now which expires at the given time
+pub fn refresh_time(now: SystemTime, expires_at: SystemTime) -> SystemTime {
+    let validity = expires_at.duration_since(now).unwrap_or(Duration::ZERO);
+    now + validity / ECR_TOKEN_REFRESH_RATIO
+}
+
+/// render the docker config json of a kubernetes.io/dockerconfigjson secret
+pub fn render_docker_config(auths: &BTreeMap<String, EcrToken>) -> Value {
+    let auths: serde_json::Map<String, Value> = auths
+        .iter()
+        .map(|(host, token)| {
+            let auth = BASE64.encode(format!("{}:{}", token.username, token.password));
+            let entry = json!({
+                "username": token.username,
+                "password": token.password,
+                "auth": auth,
+            });
+            (host.to_owned(), entry)
+        })
+        .collect();
+
+    json!({ "auths": auths })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skip_without_mock_env() -> bool {
+        if crate::aws_common::should_run_aws_integration_tests() {
+            return false;
+        }
+
+        eprintln!("Skipping AWS integration test: TEST_ENV=true is required");
+        true
+    }
+
+    #[test]
+    fn test_parse_ecr_registry() {
+        assert_eq!(parse_ecr_registry("123456789012"), (None, None));
+        assert_eq!(
+            parse_ecr_registry("123456789012.dkr.ecr.us-east-1.amazonaws.com"),
+            (
+                Some("123456789012.dkr.ecr.us-east-1.amazonaws.com".to_string()),
+                Some("us-east-1".to_string())
+            )
+        );
+        assert_eq!(
+            parse_ecr_registry("registry.example.com"),
+            (Some("registry.example.com".to_string()), None)
+        );
+    }
+
+    #[test]
+    fn test_refresh_time() {
+        let now = SystemTime::UNIX_EPOCH;
+        let expires_at = now + Duration::from_secs(12 * 60 * 60);
+
+        assert_eq!(
+            refresh_time(now, expires_at),
+            now + Duration::from_secs(6 * 60 * 60)
+        );
+        assert_eq!(refresh_time(expires_at, now), expires_at);
+    }
+
+    #[test]
+    fn test_render_docker_config() {
+        let token = EcrToken {
+            username: "AWS".to_string(),
+            password: "password".to_string(),
+            region: "us-east-1".to_string(),
+            refresh_at: SystemTime::now(),
+        };
+        let auths = BTreeMap::from([(
+            "123456789012.dkr.ecr.us-east-1.amazonaws.com".to_string(),
+            token,
+        )]);
+
+        let config = render_docker_config(&auths);
+        let auth = &config["auths"]["123456789012.dkr.ecr.us-east-1.amazonaws.com"];
+
+        assert_eq!(auth["username"], "AWS");
+        assert_eq!(auth["password"], "password");
+        assert_eq!(auth["auth"], BASE64.encode("AWS:password"));
+    }
+
+    #[test]
+    fn test_refresh_at() {
+        let refresh_at = SystemTime::now() + Duration::from_secs(60);
+        ECR_TOKENS.lock().unwrap().insert(
+            Some("eu-west-3".to_string()),
+            EcrToken {
+                username: "AWS".to_string(),
+                password: "password".to_string(),
+                region: "eu-west-3".to_string(),
+                refresh_at,
+            },
+        );
+
+        let backend: Backend = serde_json::from_str(
+            r#"{"backend": "ECRAuthToken", "data": [{"value": "123456789012.dkr.ecr.eu-west-3.amazonaws.com"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ECRAuthToken::from_backend(&backend).refresh_at(),
+            Some(refresh_at)
+        );
+
+        // the token of ap-east-2 is missing, so the backend is polled until it is fetched
+        let backend: Backend = serde_json::from_str(
+            r#"{"backend": "ECRAuthToken", "data": [{"value": "123456789012.dkr.ecr.eu-west-3.amazonaws.com"}, {"value": "123456789012", "region": "ap-east-2"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(ECRAuthToken::from_backend(&backend).refresh_at(), None);
+    }
+
+    #[tokio::test]
+    async fn test_ecr_auth_token() {
+        if skip_without_mock_env() {
+            return;
+        }
+
+        let backend_str = r#"
+        {
+            "backend": "ECRAuthToken",
+            "data": [
+                {
+                    "value": "123456789012"
+                }
+            ]
+        }"#;
+
+        let backend: Backend = serde_json::from_str(backend_str).unwrap();
+
+        let ecr = ECRAuthToken::from_backend(&backend);
+
+        let value = ecr.get_value().await.data;
+
+        assert!(value.contains_key(DOCKER_CONFIG_JSON_KEY));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config_for_region, is_test_env};
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
+use async_trait::async_trait;
+use aws_sdk_kms::primitives::Blob;
+use cached::macros::cached;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::{BTreeMap, HashMap};
+
+use utils::value::get_secret_bytes_data;
+
+#[derive(Clone, Debug)]
+pub struct KMS {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for KMS {
+    fn from_backend(backend: &Backend) -> KMS {
+        KMS {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
+            let kms_secret_data = decrypt_kms_ciphertext(
+                secret_data.value.clone(),
+                secret_data.encryption_context.clone(),
+                secret_data.region.clone(),
+            )
+            .await;
+
+            match kms_secret_data {
+                Ok(kms_secret_data) => get_secret_bytes_data(secret_data, &kms_secret_data),
+                Err(err) => {
+                    log::error!("{}", err);
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the kms backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("KMS", |backend, _| Box::new(KMS::from_backend(backend))));
+}
+
+/// get the kms client
+pub fn kms_client(conf: &aws_types::SdkConfig) -> aws_sdk_kms::Client {
+    let mut kms_config_builder = aws_sdk_kms::config::Builder::from(conf);
+    if is_test_env() {
+        log::info!("Using local AWS endpoint for KMS {}", aws_endpoint_url());
+        kms_config_builder = kms_config_builder.endpoint_url(aws_endpoint_url())
+    }
+    aws_sdk_kms::Client::from_conf(kms_config_builder.build())
+}
+
+/// decrypt a base64 kms ciphertext, the encryption context has to match the one used to encrypt
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn decrypt_kms_ciphertext(
+    ciphertext: String,
+    encryption_context: Option<BTreeMap<String, String>>,
+    region: Option<String>,
+) -> Result<Vec<u8>> {
+    let ciphertext = BASE64
+        .decode(ciphertext.split_whitespace().collect::<String>())
+        .map_err(|e| anyhow!("invalid base64 kms ciphertext: {e}"))?;
+
+    let shared_config = get_aws_sdk_config_for_region(region).await?;
+    let client = kms_client(&shared_config);
+    let output = client
+        .decrypt()
+        .ciphertext_blob(Blob::new(ciphertext))
+        .set_encryption_context(
+            encryption_context.map(|context| context.into_iter().collect::<HashMap<_, _>>()),
+        )
+        .send()
+        .await?;
+
+    let plaintext = output
+        .plaintext()
+        .ok_or_else(|| anyhow!("no plaintext found"))?;
+
+    Ok(plaintext.as_ref().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skip_without_mock_env() -> bool {
+        if crate::aws_common::should_run_aws_integration_tests() {
+            return false;
+        }
+
+        eprintln!("Skipping AWS integration test: TEST_ENV=true is required");
+        true
+    }
+
+    #[tokio::test]
+    async fn test_invalid_kms_ciphertext() {
+        let result = decrypt_kms_ciphertext("not base64!".to_string(), None, None).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_kms_ciphertext() {
+        if skip_without_mock_env() {
+            return;
+        }
+
+        let shared_config = get_aws_sdk_config_for_region(None).await.unwrap();
+        let client = kms_client(&shared_config);
+        let key = client.create_key().send().await.unwrap();
+        let key_id = key.key_metadata().unwrap().key_id();
+        let context = BTreeMap::from([("app".to_string(), "remote-secrets".to_string())]);
+
+        let encrypted = client
+            .encrypt()
+            .key_id(key_id)
+            .plaintext(Blob::new("Vick"))
+            .set_encryption_context(Some(context.clone().into_iter().collect()))
+            .send()
+            .await
+            .unwrap();
+        let ciphertext = BASE64.encode(encrypted.ciphertext_blob().unwrap().as_ref());
+
+        let result = decrypt_kms_ciphertext(ciphertext, Some(context), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result, b"Vick");
+    }
+}
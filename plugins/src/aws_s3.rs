@@ -0,0 +1,161 @@
+use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config_for_region, is_test_env};
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use utils::value::get_secret_bytes_data;
+
+#[derive(Clone, Debug)]
+pub struct S3 {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for S3 {
+    fn from_backend(backend: &Backend) -> S3 {
+        S3 {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
+            let s3_secret_data = get_s3_object_by_url(secret_data).await;
+
+            match s3_secret_data {
+                Ok(s3_secret_data) => get_secret_bytes_data(secret_data, &s3_secret_data),
+                Err(err) => {
+                    log::error!("{}", err);
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the s3 backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("S3", |backend, _| Box::new(S3::from_backend(backend))));
+}
+
+/// get the s3 client
+pub fn s3_client(conf: &aws_types::SdkConfig) -> aws_sdk_s3::Client {
+    let mut s3_config_builder = aws_sdk_s3::config::Builder::from(conf);
+    if is_test_env() {
+        log::info!("Using local AWS endpoint for S3 {}", aws_endpoint_url());
+        s3_config_builder = s3_config_builder
+            .endpoint_url(aws_endpoint_url())
+            .force_path_style(true)
+    }
+    aws_sdk_s3::Client::from_conf(s3_config_builder.build())
+}
+
+/// split an s3://bucket/key url into the bucket and the key
+pub fn parse_s3_url(url: &str) -> Result<(String, String)> {
+    let path = url
+        .strip_prefix("s3://")
+        .ok_or_else(|| anyhow!("{url} is not an s3:// url"))?;
+
+    match path.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+            Ok((bucket.to_owned(), key.to_owned()))
+        }
+        _ => Err(anyhow!("{url} should be in the format of s3://bucket/key")),
+    }
+}
+
+/// get the body of the s3 object the secret data points to
+pub async fn get_s3_object_by_url(secret_data: &SecretData) -> Result<Vec<u8>> {
+    let (bucket, key) = parse_s3_url(&secret_data.value)?;
+    get_s3_object(
+        bucket,
+        key,
+        secret_data.version_id.clone(),
+        secret_data.region.clone(),
+    )
+    .await
+}
+
+/// get the body of the s3 object, the latest version if no version id is given
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn get_s3_object(
+    bucket: String,
+    object_key: String,
+    version_id: Option<String>,
+    region: Option<String>,
+) -> Result<Vec<u8>> {
+    let shared_config = get_aws_sdk_config_for_region(region).await?;
+    let client = s3_client(&shared_config);
+    let output = client
+        .get_object()
+        .bucket(bucket)
+        .key(object_key)
+        .set_version_id(version_id)
+        .send()
+        .await?;
+    let body = output.body.collect().await?;
+
+    Ok(body.into_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skip_without_mock_env() -> bool {
+        if crate::aws_common::should_run_aws_integration_tests() {
+            return false;
+        }
+
+        eprintln!("Skipping AWS integration test: TEST_ENV=true is required");
+        true
+    }
+
+    #[tokio::test]
+    async fn test_s3() {
+        if skip_without_mock_env() {
+            return;
+        }
+
+        let backend_str = r#"
+        {
+            "backend": "S3",
+            "data": [
+                {
+                    "value": "s3://remote-secrets-test/app.env",
+                    "format": "Dotenv"
+                },
+                {
+                    "value": "s3://remote-secrets-test/terraform.tfstate",
+                    "key": "state"
+                }
+            ]
+        }"#;
+
+        let backend: Backend = serde_json::from_str(backend_str).unwrap();
+
+        let s3 = S3::from_backend(&backend);
+
+        let value = s3.get_value().await.data;
+
+        assert_eq!(value.get("S3_ENV_NAME").unwrap().0, b"s3Env");
+        assert!(value.contains_key("state"));
+    }
+
+    #[test]
+    fn test_parse_s3_url() {
+        let (bucket, key) = parse_s3_url("s3://my-bucket/path/to/state.json").unwrap();
+
+        assert_eq!(bucket, "my-bucket");
+        assert_eq!(key, "path/to/state.json");
+        assert!(parse_s3_url("s3://my-bucket").is_err());
+        assert!(parse_s3_url("file:///state.json").is_err());
+    }
+}
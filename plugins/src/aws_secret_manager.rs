@@ -1,10 +1,11 @@
 use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config, is_test_env};
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
 use async_trait::async_trait;
 use cached::macros::cached;
-use crd::{Backend, RemoteValue, SecretData};
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
 
 use anyhow::Result;
-use k8s_openapi::ByteString;
 use std::collections::BTreeMap;
 
 use utils::value::get_secret_data;
@@ -21,31 +22,29 @@ impl RemoteValue for SecretManager {
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
-
-        for secret_data in self.data.iter() {
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
             let data = get_secretsmanager_parameter(secret_data.value.clone()).await;
 
             match data {
-                Ok(data) => {
-                    let data = get_secret_data(secret_data, &data);
-
-                    secrets = data
-                        .into_iter()
-                        .chain(secrets.clone().into_iter())
-                        .collect();
-                }
+                Ok(data) => get_secret_data(secret_data, &data),
                 Err(err) => {
                     log::error!("{}", err);
+                    BTreeMap::new()
                 }
             }
-        }
-
-        secrets
+        })
+        .await
     }
 }
 
+/// registers the secret manager backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("SecretManager", |backend, _| {
+        Box::new(SecretManager::from_backend(backend))
+    }));
+}
+
 /// get the secret manager client
 pub fn secretsmanager_client(conf: &aws_types::SdkConfig) -> aws_sdk_secretsmanager::Client {
     let mut secretsmanager_config_builder = aws_sdk_secretsmanager::config::Builder::from(conf);
@@ -71,6 +70,53 @@ pub async fn get_secretsmanager_parameter(name: String) -> Result<String> {
     Ok(result.to_string())
 }
 
+/// write the value as the current version of the secret, creating the secret if missing
+pub async fn put_secretsmanager_value(name: &str, value: &str) -> Result<()> {
+    let shared_config = get_aws_sdk_config().await?;
+    let client = secretsmanager_client(&shared_config);
+    let output = client
+        .put_secret_value()
+        .secret_id(name)
+        .secret_string(value)
+        .send()
+        .await;
+
+    match output {
+        Ok(_) => Ok(()),
+        Err(err)
+            if err
+                .as_service_error()
+                .is_some_and(|err| err.is_resource_not_found_exception()) =>
+        {
+            client
+                .create_secret()
+                .name(name)
+                .secret_string(value)
+                .send()
+                .await?;
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// schedule the deletion of the secret, a missing secret is not an error
+pub async fn delete_secretsmanager_secret(name: &str) -> Result<()> {
+    let shared_config = get_aws_sdk_config().await?;
+    let client = secretsmanager_client(&shared_config);
+    match client.delete_secret().secret_id(name).send().await {
+        Ok(_) => Ok(()),
+        Err(err)
+            if err
+                .as_service_error()
+                .is_some_and(|err| err.is_resource_not_found_exception()) =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
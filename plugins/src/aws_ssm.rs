@@ -1,10 +1,11 @@
 use crate::aws_common::{aws_endpoint_url, get_aws_sdk_config, is_test_env};
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
 use async_trait::async_trait;
 use cached::macros::cached;
-use crd::{Backend, RemoteValue, SecretData};
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
 
 use anyhow::{anyhow, Result};
-use k8s_openapi::ByteString;
 use std::collections::BTreeMap;
 
 use utils::value::get_secret_data;
@@ -21,31 +22,27 @@ impl RemoteValue for SSM {
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
-
-        for secret_data in self.data.iter() {
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
             let ssm_secret_data = get_ssm_parameter(secret_data.value.clone()).await;
 
             match ssm_secret_data {
-                Ok(ssm_secret_data) => {
-                    let data = get_secret_data(secret_data, &ssm_secret_data);
-
-                    secrets = data
-                        .into_iter()
-                        .chain(secrets.clone().into_iter())
-                        .collect();
-                }
+                Ok(ssm_secret_data) => get_secret_data(secret_data, &ssm_secret_data),
                 Err(err) => {
                     log::error!("{}", err);
+                    BTreeMap::new()
                 }
             }
-        }
-
-        secrets
+        })
+        .await
     }
 }
 
+/// registers the ssm backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("SSM", |backend, _| Box::new(SSM::from_backend(backend))));
+}
+
 /// get the ssm client
 pub fn ssm_client(conf: &aws_types::SdkConfig) -> aws_sdk_ssm::Client {
     let mut ssm_config_builder = aws_sdk_ssm::config::Builder::from(conf);
@@ -76,6 +73,38 @@ pub async fn get_ssm_parameter(name: String) -> Result<String> {
     Ok(result.to_string())
 }
 
+/// write the value as a SecureString parameter, overwriting the current value
+pub async fn put_ssm_parameter(name: &str, value: &str) -> Result<()> {
+    let shared_config = get_aws_sdk_config().await?;
+    let client = ssm_client(&shared_config);
+    client
+        .put_parameter()
+        .name(name)
+        .value(value)
+        .r#type(aws_sdk_ssm::types::ParameterType::SecureString)
+        .overwrite(true)
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// delete the parameter, a missing parameter is not an error
+pub async fn delete_ssm_parameter(name: &str) -> Result<()> {
+    let shared_config = get_aws_sdk_config().await?;
+    let client = ssm_client(&shared_config);
+    match client.delete_parameter().name(name).send().await {
+        Ok(_) => Ok(()),
+        Err(err)
+            if err
+                .as_service_error()
+                .is_some_and(|err| err.is_parameter_not_found()) =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,399 @@
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use utils::value::get_secret_data;
+
+const KEY_VAULT_API_VERSION: &str = "7.4";
+const KEY_VAULT_SCOPE: &str = "https://vault.azure.net/.default";
+const KEY_VAULT_DNS_SUFFIX: &str = ".vault.azure.net";
+const DEFAULT_AUTHORITY_HOST: &str = "https://login.microsoftonline.com/";
+const JWT_BEARER_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+#[derive(Clone, Debug)]
+pub struct AzureKeyVault {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for AzureKeyVault {
+    fn from_backend(backend: &Backend) -> AzureKeyVault {
+        AzureKeyVault {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let credentials = match AzureCredentials::from_env() {
+            Ok(credentials) => credentials,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let credentials = &credentials;
+        let vault_url = std::env::var("AZURE_KEYVAULT_URL").ok();
+        let vault_url = vault_url.as_deref();
+
+        resolve_entries(&self.data, |secret_data| async move {
+            let azure_data = match get_key_vault_object_url(&secret_data.value, vault_url) {
+                Ok(url) => get_key_vault_value(url, credentials.clone()).await,
+                Err(err) => Err(err),
+            };
+
+            match azure_data {
+                Ok(azure_data) => get_secret_data(secret_data, &azure_data),
+                Err(err) => {
+                    log::error!("{err}");
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the azure key vault backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("AzureKeyVault", |backend, _| {
+        Box::new(AzureKeyVault::from_backend(backend))
+    }));
+}
+
+/// how the controller authenticates against microsoft entra id
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AzureCredential {
+    /// client credentials with a client secret
+    ClientSecret(String),
+    /// workload identity, the federated service account token is read from the file
+    FederatedTokenFile(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AzureCredentials {
+    pub authority_host: String,
+    pub tenant_id: String,
+    pub client_id: String,
+    pub credential: AzureCredential,
+}
+
+impl AzureCredentials {
+    /// read the credentials from the same environment variables as the azure sdks
+    /// AZURE_FEDERATED_TOKEN_FILE is injected by the aks workload identity webhook
+    pub fn from_env() -> Result<AzureCredentials> {
+        let credential = match std::env::var("AZURE_CLIENT_SECRET") {
+            Ok(secret) => AzureCredential::ClientSecret(secret),
+            Err(_) => AzureCredential::FederatedTokenFile(
+                std::env::var("AZURE_FEDERATED_TOKEN_FILE").map_err(|_| {
+                    anyhow!("AZURE_CLIENT_SECRET or AZURE_FEDERATED_TOKEN_FILE is required")
+                })?,
+            ),
+        };
+
+        Ok(AzureCredentials {
+            authority_host: std::env::var("AZURE_AUTHORITY_HOST")
+                .unwrap_or(DEFAULT_AUTHORITY_HOST.to_string()),
+            tenant_id: std::env::var("AZURE_TENANT_ID")?,
+            client_id: std::env::var("AZURE_CLIENT_ID")?,
+            credential,
+        })
+    }
+
+    fn token_endpoint(&self) -> String {
+        let authority_host = self.authority_host.trim_end_matches('/');
+        format!("{authority_host}/{}/oauth2/v2.0/token", self.tenant_id)
+    }
+}
+
+/// get an access token for key vault
+/// Will cache the result for 300s, tokens are valid for at least an hour
+#[cached(ttl = 300)]
+pub async fn get_azure_access_token(credentials: AzureCredentials) -> Result<String> {
+    let mut form = vec![
+        ("grant_type", "client_credentials".to_string()),
+        ("client_id", credentials.client_id.clone()),
+        ("scope", KEY_VAULT_SCOPE.to_string()),
+    ];
+    match &credentials.credential {
+        AzureCredential::ClientSecret(secret) => form.push(("client_secret", secret.clone())),
+        AzureCredential::FederatedTokenFile(path) => {
+            let assertion = std::fs::read_to_string(path)?;
+            form.push((
+                "client_assertion_type",
+                JWT_BEARER_ASSERTION_TYPE.to_string(),
+            ));
+            form.push(("client_assertion", assertion.trim().to_string()));
+        }
+    }
+
+    let response: Value = reqwest::Client::new()
+        .post(credentials.token_endpoint())
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let token = response
+        .get("access_token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("no access_token in the azure token response"))?;
+
+    Ok(token.to_owned())
+}
+
+/// value is either relative to AZURE_KEYVAULT_URL, eg secrets/db-password, keys/signing-key
+/// or certificates/tls/{version}, or the full https url of an object in a *.vault.azure.net vault
+/// the bearer token of the controller is never sent to another host
+pub fn get_key_vault_object_url(value: &str, vault_url: Option<&str>) -> Result<String> {
+    let vault_origin = vault_url
+        .map(|vault_url| reqwest::Url::parse(vault_url).map(|url| url.origin()))
+        .transpose()
+        .map_err(|err| anyhow!("invalid AZURE_KEYVAULT_URL: {err}"))?;
+
+    if value.contains("://") {
+        let url = reqwest::Url::parse(value)?;
+        let is_key_vault = url.scheme() == "https"
+            && url.port().is_none()
+            && url
+                .host_str()
+                .is_some_and(|host| host.ends_with(KEY_VAULT_DNS_SUFFIX));
+        if !is_key_vault && Some(url.origin()) != vault_origin {
+            return Err(anyhow!(
+                "{value} is not the url of an azure key vault, use an https://*{KEY_VAULT_DNS_SUFFIX} url or an object relative to AZURE_KEYVAULT_URL"
+            ));
+        }
+        return Ok(value.to_owned());
+    }
+
+    let (Some(vault_url), Some(vault_origin)) = (vault_url, vault_origin) else {
+        return Err(anyhow!(
+            "AZURE_KEYVAULT_URL is required for the relative object {value}"
+        ));
+    };
+    let url = format!(
+        "{}/{}",
+        vault_url.trim_end_matches('/'),
+        value.trim_start_matches('/')
+    );
+    // the object path can not move the request to another host
+    if reqwest::Url::parse(&url)?.origin() != vault_origin {
+        return Err(anyhow!("invalid key vault object {value}"));
+    }
+
+    Ok(url)
+}
+
+/// get the value of a secret, key or certificate
+/// secrets are returned as is, keys as their json web key and certificates as pem
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn get_key_vault_value(url: String, credentials: AzureCredentials) -> Result<String> {
+    let object_url = reqwest::Url::parse(&url)?;
+    let kind = object_url
+        .path_segments()
+        .and_then(|mut segments| segments.next())
+        .unwrap_or_default()
+        .to_owned();
+
+    let token = get_azure_access_token(credentials).await?;
+    let response: Value = reqwest::Client::new()
+        .get(object_url)
+        .query(&[("api-version", KEY_VAULT_API_VERSION)])
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    match kind.as_str() {
+        "secrets" => response
+            .get("value")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("no value found for the secret {url}")),
+        "keys" => response
+            .get("key")
+            .map(Value::to_string)
+            .ok_or_else(|| anyhow!("no key found for {url}")),
+        "certificates" => response
+            .get("cer")
+            .and_then(Value::as_str)
+            .map(der_base64_to_pem)
+            .ok_or_else(|| anyhow!("no cer found for the certificate {url}")),
+        _ => Err(anyhow!(
+            "unsupported key vault object {url}, expected secrets, keys or certificates"
+        )),
+    }
+}
+
+fn der_base64_to_pem(der: &str) -> String {
+    let lines: Vec<String> = der
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+
+    format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+        lines.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use serde_json::json;
+
+    fn start_key_vault() -> TestServer {
+        TestServer::start(vec![
+            Route::new(
+                "POST",
+                "/tenant/oauth2/v2.0/token",
+                200,
+                json!({ "access_token": "azure-token", "expires_in": 3599 }).to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/secrets/db",
+                200,
+                json!({ "value": r#"{"user": "app", "password": "hunter2"}"# }).to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/keys/signing",
+                200,
+                json!({ "key": { "kty": "RSA", "n": "abc", "e": "AQAB" } }).to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/certificates/tls",
+                200,
+                json!({ "cer": "MIIB".repeat(20) }).to_string(),
+            ),
+        ])
+    }
+
+    fn credentials(server: &TestServer, credential: AzureCredential) -> AzureCredentials {
+        AzureCredentials {
+            authority_host: format!("{}/", server.url),
+            tenant_id: "tenant".to_string(),
+            client_id: "client".to_string(),
+            credential,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_key_vault_secret() {
+        let server = start_key_vault();
+        let credentials = credentials(
+            &server,
+            AzureCredential::ClientSecret("client-secret".to_string()),
+        );
+
+        let value = get_key_vault_value(format!("{}/secrets/db", server.url), credentials)
+            .await
+            .unwrap();
+        let r_data: SecretData =
+            serde_json::from_str(r#"{"value": "secrets/db", "key": "password", "is_json_string": true, "remote_path": "password"}"#)
+                .unwrap();
+        let data = get_secret_data(&r_data, &value);
+
+        assert_eq!(data["password"].0, b"hunter2");
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("client_secret=client-secret"));
+        assert!(requests[0]
+            .body
+            .contains("scope=https%3A%2F%2Fvault.azure.net%2F.default"));
+        assert_eq!(requests[1].path, "/secrets/db?api-version=7.4");
+        assert_eq!(requests[1].headers["authorization"], "Bearer azure-token");
+    }
+
+    #[tokio::test]
+    async fn test_get_key_vault_key_and_certificate() {
+        let server = start_key_vault();
+        let token_file = std::env::temp_dir().join("remote-secrets-azure-token");
+        std::fs::write(&token_file, "federated-token\n").unwrap();
+        let credentials = credentials(
+            &server,
+            AzureCredential::FederatedTokenFile(token_file.display().to_string()),
+        );
+
+        let key = get_key_vault_value(format!("{}/keys/signing", server.url), credentials.clone())
+            .await
+            .unwrap();
+        let certificate =
+            get_key_vault_value(format!("{}/certificates/tls", server.url), credentials)
+                .await
+                .unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&key).unwrap()["kty"],
+            json!("RSA")
+        );
+        assert!(certificate.starts_with("-----BEGIN CERTIFICATE-----\nMIIB"));
+        assert_eq!(certificate.lines().nth(1).unwrap().len(), 64);
+        assert!(server.requests()[0]
+            .body
+            .contains("client_assertion=federated-token"));
+
+        std::fs::remove_file(token_file).unwrap();
+    }
+
+    #[test]
+    fn test_get_key_vault_object_url() {
+        let vault_url = Some("https://my-vault.vault.azure.net/");
+
+        assert_eq!(
+            get_key_vault_object_url("secrets/db", vault_url).unwrap(),
+            "https://my-vault.vault.azure.net/secrets/db"
+        );
+        assert_eq!(
+            get_key_vault_object_url("https://other.vault.azure.net/keys/signing", None).unwrap(),
+            "https://other.vault.azure.net/keys/signing"
+        );
+        assert!(get_key_vault_object_url("secrets/db", None).is_err());
+
+        // the bearer token is never sent outside of the key vaults
+        for value in [
+            "https://attacker.example.com/secrets/db",
+            "http://my-vault.vault.azure.net/secrets/db",
+            "https://my-vault.vault.azure.net.example.com/secrets/db",
+            "https://my-vault.vault.azure.net:8443/secrets/db",
+            "http://169.254.169.254/metadata",
+        ] {
+            assert!(
+                get_key_vault_object_url(value, vault_url).is_err(),
+                "{value}"
+            );
+        }
+
+        // private endpoints are reachable through AZURE_KEYVAULT_URL
+        assert!(get_key_vault_object_url(
+            "http://vault.internal:8080/secrets/db",
+            Some("http://vault.internal:8080")
+        )
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_key_vault_object() {
+        let server = start_key_vault();
+        let credentials = credentials(&server, AzureCredential::ClientSecret("s".to_string()));
+
+        let result =
+            get_key_vault_value(format!("{}/storage/account", server.url), credentials).await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use crd::{EntryConflict, RemoteData, SecretData};
+
+use anyhow::{anyhow, Result};
+use futures::{future::join_all, Future, FutureExt};
+use k8s_openapi::ByteString;
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use utils::decoding::decode_secret_data;
+
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_BACKEND_TIMEOUT_SECONDS: u64 = 30;
+
+lazy_static! {
+    /// requests to the backends in flight, shared by every backend and entry of every rsecret
+    static ref REQUESTS: Semaphore = Semaphore::new(get_concurrency_limit());
+}
+
+/// requests to the backends in flight at once, from BACKEND_CONCURRENCY
+pub fn get_concurrency_limit() -> usize {
+    std::env::var("BACKEND_CONCURRENCY")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// time a backend has to resolve all its entries, from BACKEND_TIMEOUT_SECONDS, 0 is rejected
+pub fn get_backend_timeout() -> Duration {
+    let seconds = std::env::var("BACKEND_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(DEFAULT_BACKEND_TIMEOUT_SECONDS);
+    Duration::from_secs(seconds)
+}
+
+/// run the request once less than BACKEND_CONCURRENCY requests are in flight
+/// only the requests themselves wait for the limit, never the backends or entries awaiting them
+pub async fn limit_request<Fut: Future>(request: Fut) -> Fut::Output {
+    let _permit = REQUESTS
+        .acquire()
+        .await
+        .expect("the request semaphore is never closed");
+    request.await
+}
+
+/// the keys produced by more than one entry
+pub fn find_entry_conflicts(entries_data: &[BTreeMap<String, ByteString>]) -> Vec<EntryConflict> {
+    let mut entries_by_key: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, entry_data) in entries_data.iter().enumerate() {
+        for key in entry_data.keys() {
+            entries_by_key.entry(key).or_default().push(index);
+        }
+    }
+
+    entries_by_key
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(key, entries)| EntryConflict {
+            key: key.to_owned(),
+            entries,
+        })
+        .collect()
+}
+
+/// resolve the entries concurrently, an entry takes precedence over the entries after it
+/// like when they were resolved one after the other, the values of an entry are decoded with its decoding_strategy
+/// the keys produced by more than one entry are returned with the merged values
+pub async fn resolve_entries<'a, F, Fut>(data: &'a [SecretData], mut resolve: F) -> RemoteData
+where
+    F: FnMut(&'a SecretData) -> Fut,
+    Fut: Future<Output = BTreeMap<String, ByteString>>,
+{
+    resolve_entries_data(data, |secret_data| {
+        resolve(secret_data).map(RemoteData::from)
+    })
+    .await
+}
+
+/// like resolve_entries, for entries which may produce a key more than once themselves,
+/// eg the outputs of several stacks, the conflicts of an entry are reported with its index
+pub async fn resolve_entries_data<'a, F, Fut>(data: &'a [SecretData], mut resolve: F) -> RemoteData
+where
+    F: FnMut(&'a SecretData) -> Fut,
+    Fut: Future<Output = RemoteData>,
+{
+    // join_all keeps the order of the entries, the semaphore queues them in that order too
+    let entries = data.iter().map(|secret_data| {
+        let entry = limit_request(resolve(secret_data));
+        async move {
+            let RemoteData { data, conflicts } = entry.await;
+            (decode_entry(secret_data, data), conflicts)
+        }
+    });
+    let (results, own_conflicts): (Vec<_>, Vec<_>) = join_all(entries).await.into_iter().unzip();
+
+    let mut entries_by_key: BTreeMap<String, Vec<usize>> = find_entry_conflicts(&results)
+        .into_iter()
+        .map(|conflict| (conflict.key, conflict.entries))
+        .collect();
+    for (index, conflicts) in own_conflicts.into_iter().enumerate() {
+        for conflict in conflicts {
+            let entries = entries_by_key.entry(conflict.key).or_default();
+            if !entries.contains(&index) {
+                entries.push(index);
+                entries.sort_unstable();
+            }
+        }
+    }
+    let conflicts = entries_by_key
+        .into_iter()
+        .map(|(key, entries)| EntryConflict { key, entries })
+        .collect();
+
+    let mut secrets = BTreeMap::new();
+    for data in results {
+        secrets = data.into_iter().chain(secrets).collect();
+    }
+
+    RemoteData {
+        data: secrets,
+        conflicts,
+    }
+}
+
+/// the values of an entry decoded with its decoding_strategy, nothing when they do not decode
+pub fn decode_entry(
+    secret_data: &SecretData,
+    data: BTreeMap<String, ByteString>,
+) -> BTreeMap<String, ByteString> {
+    decode_secret_data(secret_data, data).unwrap_or_else(|e| {
+        log::error!("{e}");
+        BTreeMap::new()
+    })
+}
+
+/// backends which do not fetch the values of an entry, like generated values or a rendered docker config,
+/// have nothing to decode so a decoding_strategy is refused instead of ignored
+pub fn reject_decoding(backend: &str, secret_data: &SecretData) -> Result<()> {
+    match secret_data.decoding_strategy {
+        Some(_) => Err(anyhow!(
+            "decoding_strategy of {} is not supported by the {backend} backend",
+            secret_data.value
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_entries_keeps_the_precedence() {
+        let data: Vec<SecretData> = ["first", "second", "third"]
+            .iter()
+            .map(|value| SecretData {
+                value: value.to_string(),
+                key: Some("password".to_string()),
+                ..SecretData::default()
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        let secrets = resolve_entries(&data, |secret_data| async move {
+            // the first entry finishes last
+            let delay = if secret_data.value == "first" {
+                300
+            } else {
+                100
+            };
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+
+            BTreeMap::from([
+                (
+                    "password".to_string(),
+                    ByteString(secret_data.value.as_bytes().to_vec()),
+                ),
+                (
+                    secret_data.value.clone(),
+                    ByteString(secret_data.value.as_bytes().to_vec()),
+                ),
+            ])
+        })
+        .await
+        .data;
+
+        assert_eq!(secrets["password"].0, b"first");
+        assert_eq!(secrets.len(), 4);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_entries_returns_the_conflicts() {
+        let data: Vec<SecretData> = ["first", "second", "third"]
+            .iter()
+            .map(|value| SecretData {
+                value: value.to_string(),
+                key: Some("password".to_string()),
+                ..SecretData::default()
+            })
+            .collect();
+
+        let resolved = resolve_entries(&data, |secret_data| async move {
+            let key = match secret_data.value.as_str() {
+                "second" => "username",
+                _ => "password",
+            };
+            BTreeMap::from([(
+                key.to_string(),
+                ByteString(secret_data.value.clone().into()),
+            )])
+        })
+        .await;
+
+        assert_eq!(resolved.data["password"].0, b"first");
+        assert_eq!(
+            resolved.conflicts,
+            vec![EntryConflict {
+                key: "password".to_string(),
+                entries: vec![0, 2],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_entries_data_reports_the_conflicts_of_an_entry() {
+        let data: Vec<SecretData> = ["stacks", "stack"]
+            .iter()
+            .map(|value| SecretData {
+                value: value.to_string(),
+                ..SecretData::default()
+            })
+            .collect();
+
+        let resolved = resolve_entries_data(&data, |secret_data| async move {
+            let conflicts = match secret_data.value.as_str() {
+                "stacks" => vec![EntryConflict {
+                    key: "bucket".to_string(),
+                    entries: vec![],
+                }],
+                _ => vec![],
+            };
+            RemoteData {
+                data: BTreeMap::from([(
+                    "bucket".to_string(),
+                    ByteString(secret_data.value.clone().into()),
+                )]),
+                conflicts,
+            }
+        })
+        .await;
+
+        assert_eq!(resolved.data["bucket"].0, b"stacks");
+        assert_eq!(
+            resolved.conflicts,
+            vec![EntryConflict {
+                key: "bucket".to_string(),
+                entries: vec![0, 1],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_entries_limits_the_requests_in_flight() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let data: Vec<SecretData> = (0..get_concurrency_limit() * 3)
+            .map(|index| SecretData {
+                value: index.to_string(),
+                key: Some(index.to_string()),
+                ..SecretData::default()
+            })
+            .collect();
+        let (in_flight, max_in_flight) = (&AtomicUsize::new(0), &AtomicUsize::new(0));
+
+        let secrets = resolve_entries(&data, |secret_data| async move {
+            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            BTreeMap::from([(secret_data.value.clone(), ByteString(vec![]))])
+        })
+        .await
+        .data;
+
+        assert_eq!(secrets.len(), data.len());
+        assert!(max_in_flight.load(Ordering::SeqCst) <= get_concurrency_limit());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_entries_decodes_the_values() {
+        let data: Vec<SecretData> = serde_json::from_str(
+            r#"[
+                {"value": "a2V5c3RvcmU=", "key": "keystore", "decoding_strategy": "Base64"},
+                {"value": "not hex", "key": "cert", "decoding_strategy": "Hex"},
+                {"value": "p@ss", "key": "password"}
+            ]"#,
+        )
+        .unwrap();
+
+        let secrets = resolve_entries(&data, |secret_data| async move {
+            BTreeMap::from([(
+                secret_data.key.clone().unwrap(),
+                ByteString(secret_data.value.as_bytes().to_vec()),
+            )])
+        })
+        .await
+        .data;
+
+        assert_eq!(secrets["keystore"].0, b"keystore");
+        assert_eq!(secrets["password"].0, b"p@ss");
+        assert!(!secrets.contains_key("cert"));
+    }
+}
@@ -0,0 +1,544 @@
+use crate::concurrent::{get_backend_timeout, resolve_entries};
+use crate::registry::{request_reconcile, Provider};
+use crate::secret_ref::get_backend_token;
+use async_trait::async_trait;
+use crd::{Backend, RemoteData, RemoteValue, SecretData, SecretKeyReference};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use k8s_openapi::ByteString;
+use kube::Client;
+use lazy_static::lazy_static;
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use utils::value::get_secret_bytes_data;
+
+const DEFAULT_CONSUL_ADDRESS: &str = "http://127.0.0.1:8500";
+/// a watch stops when its rsecret has not resolved the key for this long, eg once it is deleted
+const WATCH_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// pause before the next blocking query when consul answered without a change of the index
+const WATCH_MIN_INTERVAL: Duration = Duration::from_secs(1);
+/// backoff after failed queries, doubling from the first up to the max
+const WATCH_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const WATCH_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// blocking queries running by rsecret and key, with when the rsecret last resolved the key
+    static ref CONSUL_WATCHES: Mutex<BTreeMap<ConsulWatch, Instant>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Clone)]
+pub struct Consul {
+    data: Vec<SecretData>,
+    token_secret_ref: Option<SecretKeyReference>,
+    wait: Option<String>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+    /// name of the rsecret, its watches trigger its reconcile
+    rsecret: Option<String>,
+}
+
+#[async_trait]
+impl RemoteValue for Consul {
+    fn from_backend(backend: &Backend) -> Consul {
+        Consul {
+            data: backend.data.clone(),
+            token_secret_ref: backend.token_secret_ref.clone(),
+            wait: backend.consul_wait.clone(),
+            namespace: None,
+            client: None,
+            rsecret: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let token = match self.get_token().await {
+            Ok(token) => token,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let address =
+            std::env::var("CONSUL_HTTP_ADDR").unwrap_or(DEFAULT_CONSUL_ADDRESS.to_string());
+        let (token, address) = (&token, &address);
+
+        resolve_entries(&self.data, |secret_data| async move {
+            let query = ConsulQuery {
+                address: address.clone(),
+                path: secret_data.value.trim_start_matches('/').to_owned(),
+                token: token.clone(),
+                index: None,
+                wait: None,
+            };
+            self.watch(&query);
+
+            match get_consul_kv(&query).await {
+                Ok((entries, _)) if query.is_prefix() => {
+                    get_consul_prefix_as_secret_data(&query.path, &entries)
+                }
+                Ok((entries, _)) => match entries.first() {
+                    Some(entry) => get_secret_bytes_data(secret_data, &entry.decoded_value()),
+                    None => {
+                        log::error!("no consul key found for {}", query.path);
+                        BTreeMap::new()
+                    }
+                },
+                Err(err) => {
+                    log::error!("{err}");
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the consul backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Consul", |backend, context| {
+        Box::new(
+            Consul::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client)
+                .for_rsecret(context.name),
+        )
+    }).with_fields(&["token_secret_ref", "consul_wait"]));
+}
+
+impl Consul {
+    /// namespace the token secret is read from
+    pub fn in_namespace(mut self, namespace: &str) -> Consul {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> Consul {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// rsecret reconciled again when a watched key changes
+    pub fn for_rsecret(mut self, name: &str) -> Consul {
+        self.rsecret = Some(name.to_owned());
+        self
+    }
+
+    /// acl token from the secret reference, CONSUL_HTTP_TOKEN otherwise
+    async fn get_token(&self) -> Result<Option<String>> {
+        get_backend_token(
+            &self.token_secret_ref,
+            &self.namespace,
+            &self.client,
+            "CONSUL_HTTP_TOKEN",
+        )
+        .await
+    }
+
+    /// with a wait time the key is watched with blocking queries outside of the reconcile
+    fn watch(&self, query: &ConsulQuery) {
+        let (Some(wait), Some(namespace), Some(name)) =
+            (&self.wait, &self.namespace, &self.rsecret)
+        else {
+            return;
+        };
+        let wait = match parse_consul_wait(wait) {
+            Ok(wait) => clamp_consul_wait(wait, get_backend_timeout()),
+            Err(err) => {
+                log::error!("{err}");
+                return;
+            }
+        };
+
+        let watch = ConsulWatch {
+            namespace: namespace.clone(),
+            name: name.clone(),
+            url: query.url(),
+        };
+        let is_running = CONSUL_WATCHES
+            .lock()
+            .unwrap()
+            .insert(watch.clone(), Instant::now())
+            .is_some();
+        if !is_running {
+            let query = ConsulQuery {
+                wait: Some(wait),
+                ..query.clone()
+            };
+            tokio::spawn(run_consul_watch(self.clone(), watch, query));
+        }
+    }
+}
+
+/// a key watched for a rsecret, every rsecret has its own index
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConsulWatch {
+    pub namespace: String,
+    pub name: String,
+    pub url: String,
+}
+
+/// blocking queries until the rsecret stops resolving the key, a change of the index
+/// triggers the reconcile of the rsecret, the token is read again for every query
+async fn run_consul_watch(consul: Consul, watch: ConsulWatch, mut query: ConsulQuery) {
+    let wait = query.wait.unwrap_or_default();
+    let mut failures = 0;
+    loop {
+        let is_idle = {
+            let mut watches = CONSUL_WATCHES.lock().unwrap();
+            let is_idle = watches
+                .get(&watch)
+                .is_none_or(|last_seen| last_seen.elapsed() > WATCH_IDLE_TIMEOUT);
+            if is_idle {
+                watches.remove(&watch);
+            }
+            is_idle
+        };
+        if is_idle {
+            return;
+        }
+
+        let result = match consul.get_token().await {
+            Ok(token) => {
+                query.token = token;
+                get_consul_kv(&query).await
+            }
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok((_, Some(index))) => {
+                failures = 0;
+                let (next_index, is_changed) = next_consul_index(query.index, index);
+                if is_changed {
+                    request_reconcile(&watch.namespace, &watch.name);
+                } else if query.index.is_some() {
+                    // consul may answer before the wait time without any change
+                    tokio::time::sleep(with_jitter(WATCH_MIN_INTERVAL)).await;
+                }
+                query.index = Some(next_index);
+            }
+            Ok((_, None)) => {
+                failures = 0;
+                tokio::time::sleep(with_jitter(wait)).await;
+            }
+            Err(err) => {
+                failures += 1;
+                log::error!("failed to watch the consul key {}: {err}", query.path);
+                tokio::time::sleep(get_watch_backoff(failures)).await;
+            }
+        }
+    }
+}
+
+/// the index of the next blocking query and whether the key changed, the index goes back to 0
+/// when it went backwards, eg after a reset of the consul state, as the consul docs recommend
+pub fn next_consul_index(previous: Option<u64>, index: u64) -> (u64, bool) {
+    match previous {
+        None | Some(0) => (index, false),
+        Some(previous) if index < previous => (0, true),
+        Some(previous) => (index, index != previous),
+    }
+}
+
+/// backoff after the failures in a row, doubling from WATCH_MIN_BACKOFF up to WATCH_MAX_BACKOFF
+pub fn get_watch_backoff(failures: u32) -> Duration {
+    let backoff = WATCH_MIN_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(WATCH_MAX_BACKOFF);
+    with_jitter(backoff)
+}
+
+/// up to a quarter more, so the watches of many rsecrets do not query consul in lockstep
+fn with_jitter(duration: Duration) -> Duration {
+    let max_jitter = duration.as_millis() as u64 / 4;
+    duration + Duration::from_millis(OsRng.next_u64() % (max_jitter + 1))
+}
+
+/// wait time of consul eg 300ms, 5s or 1m
+pub fn parse_consul_wait(wait: &str) -> Result<Duration> {
+    let split = wait
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("invalid consul wait {wait}, expected a unit eg 5s"))?;
+    let (value, unit) = wait.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid consul wait {wait}"))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 60 * 60)),
+        _ => Err(anyhow!(
+            "invalid consul wait {wait}, expected ms, s, m or h"
+        )),
+    }
+}
+
+/// consul adds up to wait/16 of jitter, the wait is kept to half of the backend timeout
+/// so a blocking query never outlives it
+pub fn clamp_consul_wait(wait: Duration, backend_timeout: Duration) -> Duration {
+    wait.min(backend_timeout / 2).max(Duration::from_secs(1))
+}
+
+#[derive(Clone, Debug)]
+pub struct ConsulQuery {
+    pub address: String,
+    /// key, or key prefix ending with /
+    pub path: String,
+    pub token: Option<String>,
+    /// index of the previous query, the query blocks until it changes
+    pub index: Option<u64>,
+    /// wait time of the blocking query
+    pub wait: Option<Duration>,
+}
+
+impl ConsulQuery {
+    pub fn is_prefix(&self) -> bool {
+        self.path.is_empty() || self.path.ends_with('/')
+    }
+
+    fn url(&self) -> String {
+        format!("{}/v1/kv/{}", self.address.trim_end_matches('/'), self.path)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConsulKv {
+    pub key: String,
+    /// base64 encoded, null for folders
+    pub value: Option<String>,
+}
+
+impl ConsulKv {
+    pub fn decoded_value(&self) -> Vec<u8> {
+        self.value
+            .as_deref()
+            .and_then(|value| BASE64.decode(value).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// read the key, or every key under the prefix, with the X-Consul-Index of the response
+/// with an index the query blocks until the index changes or the wait time is over
+pub async fn get_consul_kv(query: &ConsulQuery) -> Result<(Vec<ConsulKv>, Option<u64>)> {
+    let mut params = vec![];
+    if query.is_prefix() {
+        params.push(("recurse", "true".to_string()));
+    }
+    let mut request = reqwest::Client::new().get(query.url());
+    if let (Some(index), Some(wait)) = (query.index, query.wait) {
+        params.push(("index", index.to_string()));
+        params.push(("wait", format!("{}ms", wait.as_millis())));
+        // consul answers after the wait time and up to wait/16 of jitter
+        request = request.timeout(wait + wait / 16 + Duration::from_secs(5));
+    }
+
+    request = request.query(&params);
+    if let Some(token) = &query.token {
+        request = request.header("X-Consul-Token", token);
+    }
+    let response = request.send().await?;
+
+    let index = response
+        .headers()
+        .get("X-Consul-Index")
+        .and_then(|index| index.to_str().ok())
+        .and_then(|index| index.parse().ok());
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        if query.is_prefix() {
+            return Ok((vec![], index));
+        }
+        return Err(anyhow!("no consul key found for {}", query.path));
+    }
+
+    Ok((response.error_for_status()?.json().await?, index))
+}
+
+/// keys under the prefix become secret keys, nested keys are joined with .
+/// eg app/db/host with the prefix app/ becomes db.host
+pub fn consul_key_to_secret_key(prefix: &str, key: &str) -> Option<String> {
+    let key = key.strip_prefix(prefix)?;
+    if key.is_empty() || key.ends_with('/') {
+        return None;
+    }
+
+    Some(key.replace('/', "."))
+}
+
+pub fn get_consul_prefix_as_secret_data(
+    prefix: &str,
+    entries: &[ConsulKv],
+) -> BTreeMap<String, ByteString> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let key = consul_key_to_secret_key(prefix, &entry.key)?;
+            Some((key, ByteString(entry.decoded_value())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use serde_json::json;
+
+    fn kv(key: &str, value: Option<&str>) -> serde_json::Value {
+        json!({
+            "Key": key,
+            "Value": value.map(|value| BASE64.encode(value)),
+            "ModifyIndex": 7,
+        })
+    }
+
+    fn query(server: &TestServer, path: &str, index: Option<u64>) -> ConsulQuery {
+        ConsulQuery {
+            address: server.url.clone(),
+            path: path.to_string(),
+            token: Some("acl-token".to_string()),
+            index,
+            wait: index.map(|_| Duration::from_secs(5)),
+        }
+    }
+
+    #[test]
+    fn test_consul_key_to_secret_key() {
+        assert_eq!(
+            consul_key_to_secret_key("app/", "app/db/host"),
+            Some("db.host".to_string())
+        );
+        assert_eq!(
+            consul_key_to_secret_key("app/", "app/flag"),
+            Some("flag".to_string())
+        );
+        assert_eq!(consul_key_to_secret_key("app/", "app/"), None);
+        assert_eq!(consul_key_to_secret_key("app/", "app/db/"), None);
+        assert_eq!(consul_key_to_secret_key("app/", "other/flag"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_consul_prefix() {
+        let server = TestServer::start(vec![Route::new(
+            "GET",
+            "/v1/kv/app/",
+            200,
+            json!([
+                kv("app/", None),
+                kv("app/flag", Some("on")),
+                kv("app/db/host", Some("db.internal"))
+            ])
+            .to_string(),
+        )]);
+        let query = query(&server, "app/", None);
+
+        let (entries, _) = get_consul_kv(&query).await.unwrap();
+        let data = get_consul_prefix_as_secret_data(&query.path, &entries);
+
+        assert_eq!(data.len(), 2);
+        assert_eq!(data["flag"].0, b"on");
+        assert_eq!(data["db.host"].0, b"db.internal");
+
+        let request = &server.requests()[0];
+        assert_eq!(request.path, "/v1/kv/app/?recurse=true");
+        assert_eq!(request.headers["x-consul-token"], "acl-token");
+    }
+
+    #[tokio::test]
+    async fn test_get_consul_key_with_blocking_query() {
+        let server = TestServer::start(vec![Route::new(
+            "GET",
+            "/v1/kv/app/config",
+            200,
+            json!([kv("app/config", Some(r#"{"mode": "dark"}"#))]).to_string(),
+        )
+        .with_header("X-Consul-Index", "42")]);
+
+        let (_, index) = get_consul_kv(&query(&server, "app/config", None))
+            .await
+            .unwrap();
+        assert_eq!(index, Some(42));
+        let (entries, _) = get_consul_kv(&query(&server, "app/config", index))
+            .await
+            .unwrap();
+        let r_data: SecretData = serde_json::from_str(
+            r#"{"value": "app/config", "key": "mode", "is_json_string": true, "remote_path": "mode"}"#,
+        )
+        .unwrap();
+        let data = get_secret_bytes_data(&r_data, &entries[0].decoded_value());
+
+        assert_eq!(data["mode"].0, b"dark");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/kv/app/config");
+        assert_eq!(requests[1].path, "/v1/kv/app/config?index=42&wait=5000ms");
+    }
+
+    #[test]
+    fn test_consul_wait() {
+        assert_eq!(
+            parse_consul_wait("300ms").unwrap(),
+            Duration::from_millis(300)
+        );
+        assert_eq!(parse_consul_wait("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_consul_wait("5m").unwrap(), Duration::from_secs(300));
+        assert!(parse_consul_wait("5").is_err());
+        assert!(parse_consul_wait("5d").is_err());
+
+        let timeout = Duration::from_secs(30);
+        assert_eq!(
+            clamp_consul_wait(Duration::from_secs(5), timeout),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            clamp_consul_wait(Duration::from_secs(300), timeout),
+            Duration::from_secs(15)
+        );
+    }
+
+    #[test]
+    fn test_next_consul_index() {
+        assert_eq!(next_consul_index(None, 7), (7, false));
+        assert_eq!(next_consul_index(Some(7), 7), (7, false));
+        assert_eq!(next_consul_index(Some(7), 9), (9, true));
+        // reset of the consul state
+        assert_eq!(next_consul_index(Some(9), 3), (0, true));
+        assert_eq!(next_consul_index(Some(0), 3), (3, false));
+    }
+
+    #[test]
+    fn test_watch_backoff() {
+        let within = |backoff: Duration, expected: Duration| {
+            backoff >= expected && backoff <= expected + expected / 4
+        };
+
+        assert!(within(get_watch_backoff(1), Duration::from_secs(1)));
+        assert!(within(get_watch_backoff(3), Duration::from_secs(4)));
+        assert!(within(get_watch_backoff(10), WATCH_MAX_BACKOFF));
+        assert!(within(get_watch_backoff(100), WATCH_MAX_BACKOFF));
+    }
+
+    #[tokio::test]
+    async fn test_missing_consul_key() {
+        let server = TestServer::start(vec![]);
+
+        assert!(get_consul_kv(&query(&server, "missing", None))
+            .await
+            .is_err());
+        assert!(get_consul_kv(&query(&server, "missing/", None))
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+    }
+}
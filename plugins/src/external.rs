@@ -0,0 +1,417 @@
+//! Provider protocol of the external backend, spoken over a unix socket with a sidecar.
+//!
+//! Every connection carries one request and one response, each a single line of json.
+//! The request is `{"version": 1, "method": "health"}` or
+//! `{"version": 1, "method": "get_value", "backend": {...}, "namespace": "apps"}`,
+//! the response `{"data": {"key": "base64 value"}}` or `{"error": "message"}`, the data may come with
+//! `"conflicts": [{"key": "key", "entries": [0, 1]}]` for the keys produced by more than one entry.
+
+use crate::concurrent::{find_entry_conflicts, limit_request, reject_decoding};
+use crate::registry::Provider;
+use async_trait::async_trait;
+use crd::{Backend, EntryConflict, RemoteData, RemoteValue};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::ByteString;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// version of the provider protocol, sent with every request
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const DEFAULT_SOCKET_DIR: &str = "/var/run/remote-secrets/providers";
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Clone, Debug)]
+pub struct External {
+    backend: Backend,
+    namespace: Option<String>,
+}
+
+#[async_trait]
+impl RemoteValue for External {
+    fn from_backend(backend: &Backend) -> External {
+        External {
+            backend: backend.clone(),
+            namespace: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let Some(provider) = &self.backend.external else {
+            log::error!("no provider for the external backend");
+            return RemoteData::default();
+        };
+        // the provider answers for the whole backend, the values can not be told apart by entry
+        let rejected = self
+            .backend
+            .data
+            .iter()
+            .find_map(|secret_data| reject_decoding("External", secret_data).err());
+        if let Some(err) = rejected {
+            log::error!("{err}");
+            return RemoteData::default();
+        }
+        let timeout =
+            Duration::from_secs(provider.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS));
+
+        let socket = match get_provider_socket(&provider.name) {
+            Ok(socket) => socket,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        if let Err(err) = limit_request(check_provider_health(&socket, timeout)).await {
+            log::error!("provider {} is not healthy: {err}", provider.name);
+            return RemoteData::default();
+        }
+
+        let namespace = self.namespace.clone().unwrap_or_default();
+        match limit_request(get_external_value(
+            &socket,
+            &self.backend,
+            &namespace,
+            timeout,
+        ))
+        .await
+        {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("provider {}: {err}", provider.name);
+                RemoteData::default()
+            }
+        }
+    }
+}
+
+/// registers the external backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("External", |backend, context| {
+        Box::new(External::from_backend(backend).in_namespace(context.namespace))
+    }).with_required_fields(&["external"]));
+}
+
+impl External {
+    /// namespace of the rsecret, passed on to the provider
+    pub fn in_namespace(mut self, namespace: &str) -> External {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum ProviderRequest {
+    /// whether the provider is ready to serve
+    Health,
+    /// secret data of the backend of a rsecret in the namespace
+    GetValue {
+        backend: Box<Backend>,
+        namespace: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ProviderMessage {
+    version: u32,
+    #[serde(flatten)]
+    request: ProviderRequest,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProviderResponse {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<String, ByteString>,
+
+    /// keys produced by more than one entry of the backend
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<EntryConflict>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// socket of the provider in PROVIDER_SOCKET_DIR, shared with the sidecar
+pub fn get_provider_socket(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(anyhow!("invalid provider name {name}"));
+    }
+
+    let socket_dir = std::env::var("PROVIDER_SOCKET_DIR").unwrap_or(DEFAULT_SOCKET_DIR.to_owned());
+    Ok(Path::new(&socket_dir).join(format!("{name}.sock")))
+}
+
+/// send one request to the provider and read its response, within the timeout
+pub async fn call_provider(
+    socket: &Path,
+    request: ProviderRequest,
+    timeout: Duration,
+) -> Result<ProviderResponse> {
+    let exchange = async {
+        let mut stream = UnixStream::connect(socket).await?;
+
+        let mut line = serde_json::to_vec(&ProviderMessage {
+            version: PROTOCOL_VERSION,
+            request,
+        })?;
+        line.push(b'\n');
+        stream.write_all(&line).await?;
+
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).await?;
+        if response.is_empty() {
+            return Err(anyhow!(
+                "the provider closed the connection without a response"
+            ));
+        }
+
+        let response: ProviderResponse = serde_json::from_str(&response)?;
+        match response.error {
+            Some(error) => Err(anyhow!("{error}")),
+            None => Ok(response),
+        }
+    };
+
+    tokio::time::timeout(timeout, exchange).await.map_err(|_| {
+        anyhow!(
+            "no response from {} within {}s",
+            socket.display(),
+            timeout.as_secs_f32()
+        )
+    })?
+}
+
+pub async fn check_provider_health(socket: &Path, timeout: Duration) -> Result<()> {
+    call_provider(socket, ProviderRequest::Health, timeout).await?;
+    Ok(())
+}
+
+pub async fn get_external_value(
+    socket: &Path,
+    backend: &Backend,
+    namespace: &str,
+    timeout: Duration,
+) -> Result<RemoteData> {
+    let request = ProviderRequest::GetValue {
+        backend: Box::new(backend.clone()),
+        namespace: namespace.to_owned(),
+    };
+
+    let response = call_provider(socket, request, timeout).await?;
+    Ok(RemoteData {
+        data: response.data,
+        conflicts: response.conflicts,
+    })
+}
+
+/// provider side of the protocol, for sidecars written in rust
+#[async_trait]
+pub trait ProviderHandler: Send + Sync + 'static {
+    async fn health(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_value(&self, backend: &Backend, namespace: &str) -> Result<RemoteData>;
+}
+
+/// serve the requests of the controller on the listener, one task per connection
+pub async fn serve<H: ProviderHandler>(listener: UnixListener, handler: Arc<H>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, handler.as_ref()).await {
+                log::error!("failed to answer the controller: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection<H: ProviderHandler>(stream: UnixStream, handler: &H) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let result = match serde_json::from_str::<ProviderMessage>(&line) {
+        Ok(message) if message.version != PROTOCOL_VERSION => Err(anyhow!(
+            "unsupported protocol version {}, expected {PROTOCOL_VERSION}",
+            message.version
+        )),
+        Ok(message) => match message.request {
+            ProviderRequest::Health => handler.health().await.map(|_| RemoteData::default()),
+            ProviderRequest::GetValue { backend, namespace } => {
+                handler.get_value(&backend, &namespace).await
+            }
+        },
+        Err(err) => Err(anyhow!("invalid request: {err}")),
+    };
+
+    let response = match result {
+        Ok(RemoteData { data, conflicts }) => ProviderResponse {
+            data,
+            conflicts,
+            error: None,
+        },
+        Err(err) => ProviderResponse {
+            error: Some(err.to_string()),
+            ..ProviderResponse::default()
+        },
+    };
+
+    let mut response = serde_json::to_vec(&response)?;
+    response.push(b'\n');
+    writer.write_all(&response).await?;
+    Ok(())
+}
+
+/// reference provider, answers with the value of every secret data under its key like plaintext
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceProvider;
+
+#[async_trait]
+impl ProviderHandler for ReferenceProvider {
+    async fn get_value(&self, backend: &Backend, _namespace: &str) -> Result<RemoteData> {
+        let entries = backend
+            .data
+            .iter()
+            .map(|secret_data| {
+                let key = secret_data
+                    .key
+                    .clone()
+                    .ok_or_else(|| anyhow!("no key for the value {}", secret_data.value))?;
+                Ok(BTreeMap::from([(
+                    key,
+                    ByteString(secret_data.value.as_bytes().to_vec()),
+                )]))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // the first entry producing a key wins, like the other backends
+        Ok(RemoteData {
+            conflicts: find_entry_conflicts(&entries),
+            data: entries.into_iter().rev().flatten().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let socket =
+            std::env::temp_dir().join(format!("remote-secrets-{}-{name}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        socket
+    }
+
+    fn start_provider<H: ProviderHandler>(name: &str, handler: H) -> PathBuf {
+        let socket = socket_path(name);
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(serve(listener, Arc::new(handler)));
+        socket
+    }
+
+    fn external_backend() -> Backend {
+        serde_json::from_value(serde_json::json!({
+            "backend": "External",
+            "external": { "name": "inhouse" },
+            "data": [{ "value": "s3cr3t", "key": "password" }]
+        }))
+        .unwrap()
+    }
+
+    struct UnhealthyProvider;
+
+    #[async_trait]
+    impl ProviderHandler for UnhealthyProvider {
+        async fn health(&self) -> Result<()> {
+            Err(anyhow!("store unreachable"))
+        }
+
+        async fn get_value(&self, _backend: &Backend, _namespace: &str) -> Result<RemoteData> {
+            Err(anyhow!("store unreachable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_value_from_reference_provider() {
+        let socket = start_provider("reference", ReferenceProvider);
+        let timeout = Duration::from_secs(5);
+
+        check_provider_health(&socket, timeout).await.unwrap();
+        let remote = get_external_value(&socket, &external_backend(), "apps", timeout)
+            .await
+            .unwrap();
+
+        assert_eq!(remote.data["password"].0, b"s3cr3t");
+        assert!(remote.conflicts.is_empty());
+
+        let mut backend = external_backend();
+        backend.data.push(crd::SecretData {
+            value: "other".to_string(),
+            key: Some("password".to_string()),
+            ..crd::SecretData::default()
+        });
+        let remote = get_external_value(&socket, &backend, "apps", timeout)
+            .await
+            .unwrap();
+
+        assert_eq!(remote.data["password"].0, b"s3cr3t");
+        assert_eq!(
+            remote.conflicts,
+            vec![EntryConflict {
+                key: "password".to_string(),
+                entries: vec![0, 1],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_errors() {
+        let socket = start_provider("unhealthy", UnhealthyProvider);
+        let timeout = Duration::from_secs(5);
+
+        let err = check_provider_health(&socket, timeout).await.unwrap_err();
+        assert_eq!(err.to_string(), "store unreachable");
+        assert!(
+            get_external_value(&socket, &external_backend(), "apps", timeout)
+                .await
+                .is_err()
+        );
+
+        let missing = socket_path("missing");
+        assert!(check_provider_health(&missing, timeout).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provider_timeout() {
+        let socket = socket_path("silent");
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+
+        let err = check_provider_health(&socket, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("no response from"));
+    }
+
+    #[test]
+    fn test_provider_socket() {
+        assert!(get_provider_socket("inhouse")
+            .unwrap()
+            .ends_with("inhouse.sock"));
+        assert!(get_provider_socket("../inhouse").is_err());
+        assert!(get_provider_socket("").is_err());
+    }
+}
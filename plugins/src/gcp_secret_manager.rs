@@ -0,0 +1,476 @@
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL},
+    Engine,
+};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use utils::value::get_secret_bytes_data;
+
+const SECRET_MANAGER_ENDPOINT: &str = "https://secretmanager.googleapis.com";
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const JWT_BEARER_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+#[derive(Clone, Debug)]
+pub struct GCPSecretManager {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for GCPSecretManager {
+    fn from_backend(backend: &Backend) -> GCPSecretManager {
+        GCPSecretManager {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let credentials = match GcpCredentials::from_env() {
+            Ok(credentials) => credentials,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let endpoint = std::env::var("GCP_SECRET_MANAGER_ENDPOINT")
+            .unwrap_or(SECRET_MANAGER_ENDPOINT.to_string());
+        let (credentials, endpoint) = (&credentials, &endpoint);
+
+        resolve_entries(&self.data, |secret_data| async move {
+            let gcp_data = match get_secret_version_name(secret_data) {
+                Ok(name) => {
+                    access_secret_version(endpoint.clone(), name, credentials.clone()).await
+                }
+                Err(err) => Err(err),
+            };
+
+            match gcp_data {
+                Ok(gcp_data) => get_secret_bytes_data(secret_data, &gcp_data),
+                Err(err) => {
+                    log::error!("{err}");
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the gcp secret manager backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("GCPSecretManager", |backend, _| {
+        Box::new(GCPSecretManager::from_backend(backend))
+    }));
+}
+
+/// credentials file referenced by GOOGLE_APPLICATION_CREDENTIALS,
+/// either a service account key or a workload identity federation configuration
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+pub struct GcpCredentials {
+    #[serde(rename = "type")]
+    pub credentials_type: String,
+
+    // service_account
+    pub client_email: Option<String>,
+    pub private_key: Option<String>,
+    pub token_uri: Option<String>,
+
+    // external_account
+    pub audience: Option<String>,
+    pub subject_token_type: Option<String>,
+    pub token_url: Option<String>,
+    pub service_account_impersonation_url: Option<String>,
+    pub credential_source: Option<CredentialSource>,
+}
+
+/// where the workload identity federation subject token is read from
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+pub struct CredentialSource {
+    pub file: Option<String>,
+}
+
+impl GcpCredentials {
+    pub fn from_env() -> Result<GcpCredentials> {
+        let path = std::env::var("GOOGLE_APPLICATION_CREDENTIALS")
+            .map_err(|_| anyhow!("GOOGLE_APPLICATION_CREDENTIALS is required"))?;
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// value is the secret name, projects/{project}/secrets/{name}
+/// or projects/{project}/secrets/{name}/versions/{version}
+/// the project of a bare name is read from GOOGLE_CLOUD_PROJECT
+/// version_id pins the version when value has none, defaults to latest
+pub fn get_secret_version_name(secret_data: &SecretData) -> Result<String> {
+    let value = secret_data.value.trim_matches('/');
+    if value.contains("/versions/") {
+        return Ok(value.to_owned());
+    }
+
+    let secret = if value.starts_with("projects/") {
+        value.to_owned()
+    } else {
+        let project = std::env::var("GOOGLE_CLOUD_PROJECT")
+            .map_err(|_| anyhow!("GOOGLE_CLOUD_PROJECT is required for the secret {value}"))?;
+        format!("projects/{project}/secrets/{value}")
+    };
+    let version = secret_data.version_id.as_deref().unwrap_or("latest");
+
+    Ok(format!("{secret}/versions/{version}"))
+}
+
+/// get an access token for the secret manager api
+/// Will cache the result for 300s, tokens are valid for an hour
+#[cached(ttl = 300)]
+pub async fn get_gcp_access_token(credentials: GcpCredentials) -> Result<String> {
+    match credentials.credentials_type.as_str() {
+        "service_account" => get_service_account_token(&credentials).await,
+        "external_account" => get_external_account_token(&credentials).await,
+        other => Err(anyhow!("unsupported gcp credentials type {other}")),
+    }
+}
+
+/// exchange a self signed jwt for an access token
+async fn get_service_account_token(credentials: &GcpCredentials) -> Result<String> {
+    let token_uri = credentials
+        .token_uri
+        .clone()
+        .unwrap_or("https://oauth2.googleapis.com/token".to_string());
+    let client_email = credentials
+        .client_email
+        .as_deref()
+        .ok_or_else(|| anyhow!("no client_email in the service account key"))?;
+    let private_key = credentials
+        .private_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("no private_key in the service account key"))?;
+
+    let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let claims = json!({
+        "iss": client_email,
+        "scope": CLOUD_PLATFORM_SCOPE,
+        "aud": token_uri,
+        "iat": iat,
+        "exp": iat + 3600,
+    });
+    let assertion = sign_jwt(&claims, private_key)?;
+
+    let response: Value = reqwest::Client::new()
+        .post(&token_uri)
+        .form(&[
+            ("grant_type", JWT_BEARER_GRANT_TYPE),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    get_token_field(&response, "access_token")
+}
+
+/// exchange the federated subject token at the security token service,
+/// then impersonate the service account if configured
+async fn get_external_account_token(credentials: &GcpCredentials) -> Result<String> {
+    let token_url = credentials
+        .token_url
+        .clone()
+        .unwrap_or("https://sts.googleapis.com/v1/token".to_string());
+    let subject_token_file = credentials
+        .credential_source
+        .as_ref()
+        .and_then(|source| source.file.as_deref())
+        .ok_or_else(|| anyhow!("only file credential sources are supported"))?;
+    let subject_token = std::fs::read_to_string(subject_token_file)?;
+
+    let response: Value = reqwest::Client::new()
+        .post(&token_url)
+        .form(&[
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            (
+                "audience",
+                credentials.audience.as_deref().unwrap_or_default(),
+            ),
+            ("scope", CLOUD_PLATFORM_SCOPE),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", subject_token.trim()),
+            (
+                "subject_token_type",
+                credentials
+                    .subject_token_type
+                    .as_deref()
+                    .unwrap_or_default(),
+            ),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let federated_token = get_token_field(&response, "access_token")?;
+
+    let Some(impersonation_url) = &credentials.service_account_impersonation_url else {
+        return Ok(federated_token);
+    };
+
+    let response: Value = reqwest::Client::new()
+        .post(impersonation_url)
+        .bearer_auth(federated_token)
+        .json(&json!({ "scope": [CLOUD_PLATFORM_SCOPE] }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    get_token_field(&response, "accessToken")
+}
+
+fn get_token_field(response: &Value, field: &str) -> Result<String> {
+    response
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("no {field} in the gcp token response"))
+}
+
+/// RS256 signed jwt
+fn sign_jwt(claims: &Value, private_key: &str) -> Result<String> {
+    let header = BASE64_URL.encode(json!({ "alg": "RS256", "typ": "JWT" }).to_string());
+    let payload = BASE64_URL.encode(claims.to_string());
+    let message = format!("{header}.{payload}");
+
+    // service account keys are pkcs8, pkcs1 keys are accepted too
+    let key = RsaPrivateKey::from_pkcs8_pem(private_key)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(private_key))
+        .map_err(|e| anyhow!("invalid gcp private key: {e}"))?;
+    let signature = SigningKey::<Sha256>::new(key).sign(message.as_bytes());
+    let signature = BASE64_URL.encode(signature.to_bytes());
+
+    Ok(format!("{message}.{signature}"))
+}
+
+/// get the payload of the secret version
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn access_secret_version(
+    endpoint: String,
+    name: String,
+    credentials: GcpCredentials,
+) -> Result<Vec<u8>> {
+    let token = get_gcp_access_token(credentials).await?;
+    let endpoint = endpoint.trim_end_matches('/');
+
+    let response: Value = reqwest::Client::new()
+        .get(format!("{endpoint}/v1/{name}:access"))
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let data = response
+        .pointer("/payload/data")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("no payload found for {name}"))?;
+
+    Ok(BASE64.decode(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use rand_core::OsRng;
+    use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+
+    const SECRET_VERSION: &str = "projects/my-project/secrets/db/versions/latest";
+
+    fn start_secret_manager() -> TestServer {
+        TestServer::start(vec![
+            Route::new(
+                "POST",
+                "/token",
+                200,
+                json!({ "access_token": "sa-token", "expires_in": 3599 }).to_string(),
+            ),
+            Route::new(
+                "POST",
+                "/v1/token",
+                200,
+                json!({ "access_token": "federated-token" }).to_string(),
+            ),
+            Route::new(
+                "POST",
+                "/v1/projects/-/serviceAccounts/app@my-project.iam.gserviceaccount.com:generateAccessToken",
+                200,
+                json!({ "accessToken": "impersonated-token" }).to_string(),
+            ),
+            Route::new(
+                "GET",
+                &format!("/v1/{SECRET_VERSION}:access"),
+                200,
+                json!({
+                    "name": SECRET_VERSION,
+                    "payload": { "data": BASE64.encode(r#"{"user": "app", "password": "hunter2"}"#) }
+                })
+                .to_string(),
+            ),
+        ])
+    }
+
+    fn service_account_credentials(server: &TestServer) -> GcpCredentials {
+        let private_key = RsaPrivateKey::new(&mut OsRng, 2048)
+            .unwrap()
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap();
+
+        serde_json::from_value(json!({
+            "type": "service_account",
+            "client_email": "app@my-project.iam.gserviceaccount.com",
+            "private_key": private_key.as_str(),
+            "token_uri": format!("{}/token", server.url),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sign_jwt() {
+        use rsa::pkcs1v15::{Signature, VerifyingKey};
+        use rsa::signature::Verifier;
+
+        let private_key = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        let pem = private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+
+        let jwt = sign_jwt(&json!({ "iss": "app" }), &pem).unwrap();
+        let (message, signature) = jwt.rsplit_once('.').unwrap();
+        let signature =
+            Signature::try_from(BASE64_URL.decode(signature).unwrap().as_slice()).unwrap();
+        VerifyingKey::<Sha256>::new(private_key.to_public_key())
+            .verify(message.as_bytes(), &signature)
+            .unwrap();
+
+        assert!(sign_jwt(&json!({}), "not a key").is_err());
+    }
+
+    #[test]
+    fn test_get_secret_version_name() {
+        let mut secret_data = SecretData {
+            value: "projects/my-project/secrets/db".to_string(),
+            ..SecretData::default()
+        };
+        assert_eq!(
+            get_secret_version_name(&secret_data).unwrap(),
+            SECRET_VERSION
+        );
+
+        secret_data.version_id = Some("3".to_string());
+        assert_eq!(
+            get_secret_version_name(&secret_data).unwrap(),
+            "projects/my-project/secrets/db/versions/3"
+        );
+
+        secret_data.value = "projects/my-project/secrets/db/versions/5".to_string();
+        assert_eq!(
+            get_secret_version_name(&secret_data).unwrap(),
+            "projects/my-project/secrets/db/versions/5"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_secret_version_with_service_account() {
+        let server = start_secret_manager();
+        let credentials = service_account_credentials(&server);
+
+        let payload =
+            access_secret_version(server.url.clone(), SECRET_VERSION.to_string(), credentials)
+                .await
+                .unwrap();
+        let r_data: SecretData = serde_json::from_str(
+            r#"{"value": "db", "key": "password", "is_json_string": true, "remote_path": "password"}"#,
+        )
+        .unwrap();
+        let data = get_secret_bytes_data(&r_data, &payload);
+
+        assert_eq!(data["password"].0, b"hunter2");
+
+        let requests = server.requests();
+        assert!(requests[0]
+            .body
+            .contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer"));
+        assert_eq!(requests[1].headers["authorization"], "Bearer sa-token");
+    }
+
+    #[tokio::test]
+    async fn test_access_secret_version_with_workload_identity() {
+        let server = start_secret_manager();
+        let token_file = std::env::temp_dir().join("remote-secrets-gcp-token");
+        std::fs::write(&token_file, "k8s-token\n").unwrap();
+        let credentials: GcpCredentials = serde_json::from_value(json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/pool/providers/k8s",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": format!("{}/v1/token", server.url),
+            "service_account_impersonation_url": format!(
+                "{}/v1/projects/-/serviceAccounts/app@my-project.iam.gserviceaccount.com:generateAccessToken",
+                server.url
+            ),
+            "credential_source": { "file": token_file.display().to_string() },
+        }))
+        .unwrap();
+
+        let payload =
+            access_secret_version(server.url.clone(), SECRET_VERSION.to_string(), credentials)
+                .await
+                .unwrap();
+
+        assert!(String::from_utf8(payload).unwrap().contains("hunter2"));
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("subject_token=k8s-token"));
+        assert_eq!(
+            requests[1].headers["authorization"],
+            "Bearer federated-token"
+        );
+        assert_eq!(
+            requests[2].headers["authorization"],
+            "Bearer impersonated-token"
+        );
+
+        std::fs::remove_file(token_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_secret_version() {
+        let server = start_secret_manager();
+        let credentials = service_account_credentials(&server);
+
+        let result = access_secret_version(
+            server.url.clone(),
+            "projects/my-project/secrets/missing/versions/latest".to_string(),
+            credentials,
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+}
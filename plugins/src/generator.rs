@@ -0,0 +1,543 @@
+use crate::concurrent::{reject_decoding, resolve_entries};
+use crate::registry::Provider;
+use async_trait::async_trait;
+use crd::{
+    Backend, CharacterClass, GeneratorKind, GeneratorSpec, KeyRewrite, RemoteData, RemoteValue,
+    SecretData,
+};
+
+use anyhow::{anyhow, Result};
+use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey, EncodePublicKey};
+use ed25519_dalek::SigningKey;
+use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::ByteString;
+use rand_core::{OsRng, RngCore};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use std::collections::BTreeMap;
+use utils::rewrite::rewrite_keys;
+
+/// annotation of the secret recording the rotation a generated key was generated for
+pub const ROTATION_ANNOTATION_PREFIX: &str = "generator.jerry153fish.com/";
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+// no quotes, backslash or space so passwords can be used in connection strings and shells
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+const DEFAULT_LENGTH: u32 = 32;
+const MAX_LENGTH: u32 = 4096;
+const DEFAULT_RSA_BITS: u32 = 4096;
+const RSA_BITS: [u32; 3] = [2048, 3072, 4096];
+
+#[derive(Clone, Debug)]
+pub struct Generator {
+    data: Vec<SecretData>,
+    /// rewrite rules of the backend, the existing values are stored under the rewritten keys
+    rewrite: Vec<KeyRewrite>,
+    existing: Option<Secret>,
+}
+
+#[async_trait]
+impl RemoteValue for Generator {
+    fn from_backend(backend: &Backend) -> Generator {
+        Generator {
+            data: backend.data.clone(),
+            rewrite: backend.rewrite.clone().unwrap_or_default(),
+            existing: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
+            let Some(spec) = &secret_data.generator else {
+                log::error!("no generator for {}", secret_data.value);
+                return BTreeMap::new();
+            };
+            if let Err(err) = reject_decoding("Generator", secret_data) {
+                log::error!("{err}");
+                return BTreeMap::new();
+            }
+            let key = secret_data.key.clone().unwrap_or(secret_data.value.clone());
+
+            let data = match self.get_existing_value(&key, spec) {
+                Some(data) => Ok(data),
+                None => generate_on_blocking_thread(key.clone(), spec.clone()).await,
+            };
+
+            data.unwrap_or_else(|err| {
+                log::error!("failed to generate {key}: {err}");
+                BTreeMap::new()
+            })
+        })
+        .await
+    }
+}
+
+/// registers the generator backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Generator", |backend, context| {
+        Box::new(Generator::from_backend(backend).with_existing(context.existing))
+    }));
+}
+
+impl Generator {
+    /// secret of the rsecret holding the values generated by earlier reconciles
+    pub fn with_existing(mut self, secret: Option<&Secret>) -> Generator {
+        self.existing = secret.cloned();
+        self
+    }
+
+    /// the generated values stored in the secret, unless the rotation changed since
+    fn get_existing_value(
+        &self,
+        key: &str,
+        spec: &GeneratorSpec,
+    ) -> Option<BTreeMap<String, ByteString>> {
+        let existing = self.existing.as_ref()?;
+
+        if let Some(rotation) = &spec.rotation {
+            let generated_for = existing
+                .metadata
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(&rotation_annotation(key)));
+            if generated_for != Some(rotation) {
+                return None;
+            }
+        }
+
+        let data = existing.data.as_ref()?;
+        generated_keys(key, &spec.kind)
+            .into_iter()
+            .map(|key| {
+                let stored_key = self.stored_key(&key)?;
+                data.get(&stored_key).map(|value| (key, value.clone()))
+            })
+            .collect()
+    }
+
+    /// key of the secret a generated key ends up under after the rewrite of the backend
+    fn stored_key(&self, key: &str) -> Option<String> {
+        let key = BTreeMap::from([(key.to_owned(), ByteString(Vec::new()))]);
+        rewrite_keys(key, &self.rewrite).ok()?.into_keys().next()
+    }
+}
+
+/// annotations recording the rotation of every generated key of the backend
+pub fn get_rotation_annotations(backend: &Backend) -> BTreeMap<String, String> {
+    backend
+        .data
+        .iter()
+        .filter_map(|secret_data| {
+            let rotation = secret_data.generator.as_ref()?.rotation.clone()?;
+            let key = secret_data.key.clone().unwrap_or(secret_data.value.clone());
+            Some((rotation_annotation(&key), rotation))
+        })
+        .collect()
+}
+
+fn rotation_annotation(key: &str) -> String {
+    format!("{ROTATION_ANNOTATION_PREFIX}{key}")
+}
+
+/// keypairs store the public key next to the private key
+fn generated_keys(key: &str, kind: &GeneratorKind) -> Vec<String> {
+    match kind {
+        GeneratorKind::RSA | GeneratorKind::Ed25519 => vec![key.to_owned(), format!("{key}.pub")],
+        _ => vec![key.to_owned()],
+    }
+}
+
+/// rsa keys take seconds to generate, so the generation runs on the blocking threads
+/// instead of holding up the other reconciles of the tokio worker
+async fn generate_on_blocking_thread(
+    key: String,
+    spec: GeneratorSpec,
+) -> Result<BTreeMap<String, ByteString>> {
+    tokio::task::spawn_blocking(move || generate_secret_data(&key, &spec)).await?
+}
+
+pub fn generate_secret_data(
+    key: &str,
+    spec: &GeneratorSpec,
+) -> Result<BTreeMap<String, ByteString>> {
+    let length = spec.length.unwrap_or(DEFAULT_LENGTH);
+    if length > MAX_LENGTH {
+        return Err(anyhow!(
+            "length {length} is over the maximum of {MAX_LENGTH}"
+        ));
+    }
+    let length = length as usize;
+
+    let (private_key, public_key) = match spec.kind {
+        GeneratorKind::Password => {
+            let password = generate_password(length, &spec.charset, &spec.classes)?;
+            return Ok(BTreeMap::from([(
+                key.to_owned(),
+                ByteString(password.into_bytes()),
+            )]));
+        }
+        GeneratorKind::UUID => {
+            return Ok(BTreeMap::from([(
+                key.to_owned(),
+                ByteString(generate_uuid()?.into_bytes()),
+            )]));
+        }
+        GeneratorKind::Bytes => {
+            let mut bytes = vec![0; length];
+            random_bytes(&mut bytes)?;
+            return Ok(BTreeMap::from([(key.to_owned(), ByteString(bytes))]));
+        }
+        GeneratorKind::RSA => {
+            let bits = spec.bits.unwrap_or(DEFAULT_RSA_BITS);
+            if !RSA_BITS.contains(&bits) {
+                return Err(anyhow!(
+                    "rsa keys of {bits} bits are not supported, use 2048, 3072 or 4096"
+                ));
+            }
+            let private_key = RsaPrivateKey::new(&mut OsRng, bits as usize)?;
+            let public_key = RsaPublicKey::from(&private_key);
+            (
+                rsa::pkcs8::EncodePrivateKey::to_pkcs8_pem(&private_key, LineEnding::LF)?
+                    .to_string(),
+                rsa::pkcs8::EncodePublicKey::to_public_key_pem(&public_key, LineEnding::LF)?,
+            )
+        }
+        GeneratorKind::Ed25519 => {
+            let signing_key = SigningKey::generate(&mut OsRng);
+            (
+                signing_key.to_pkcs8_pem(LineEnding::LF)?.to_string(),
+                signing_key
+                    .verifying_key()
+                    .to_public_key_pem(LineEnding::LF)?,
+            )
+        }
+    };
+
+    Ok(BTreeMap::from([
+        (key.to_owned(), ByteString(private_key.into_bytes())),
+        (format!("{key}.pub"), ByteString(public_key.into_bytes())),
+    ]))
+}
+
+/// password of the charset with at least one character of every required class
+pub fn generate_password(
+    length: usize,
+    charset: &Option<String>,
+    classes: &Option<Vec<CharacterClass>>,
+) -> Result<String> {
+    let classes = match (charset, classes) {
+        (_, Some(classes)) => classes.clone(),
+        (Some(_), None) => Vec::new(),
+        (None, None) => vec![
+            CharacterClass::Lowercase,
+            CharacterClass::Uppercase,
+            CharacterClass::Digits,
+            CharacterClass::Symbols,
+        ],
+    };
+
+    let charset: Vec<char> = match charset {
+        Some(charset) => charset.chars().collect(),
+        None => classes
+            .iter()
+            .flat_map(|class| class_characters(class).chars())
+            .collect(),
+    };
+    if charset.is_empty() {
+        return Err(anyhow!("no characters to generate the password from"));
+    }
+    if length < classes.len() {
+        return Err(anyhow!(
+            "password length {length} is shorter than the {} required classes",
+            classes.len()
+        ));
+    }
+
+    let mut password = Vec::with_capacity(length);
+    for class in classes.iter() {
+        let class_charset: Vec<char> = charset
+            .iter()
+            .filter(|c| class_characters(class).contains(**c))
+            .copied()
+            .collect();
+        if class_charset.is_empty() {
+            return Err(anyhow!("the charset has no {class:?} characters"));
+        }
+        password.push(class_charset[random_index(class_charset.len())?]);
+    }
+    while password.len() < length {
+        password.push(charset[random_index(charset.len())?]);
+    }
+
+    // fisher-yates, so the required characters are not always at the start
+    for i in (1..password.len()).rev() {
+        password.swap(i, random_index(i + 1)?);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+fn class_characters(class: &CharacterClass) -> &'static str {
+    match class {
+        CharacterClass::Lowercase => LOWERCASE,
+        CharacterClass::Uppercase => UPPERCASE,
+        CharacterClass::Digits => DIGITS,
+        CharacterClass::Symbols => SYMBOLS,
+    }
+}
+
+fn random_bytes(bytes: &mut [u8]) -> Result<()> {
+    OsRng
+        .try_fill_bytes(bytes)
+        .map_err(|err| anyhow!("no randomness available: {err}"))
+}
+
+/// uniform random index below n, rejecting the values that would bias the modulo
+fn random_index(n: usize) -> Result<usize> {
+    let n = n as u32;
+    let limit = u32::MAX - u32::MAX % n;
+    loop {
+        let mut bytes = [0; 4];
+        random_bytes(&mut bytes)?;
+        let value = u32::from_le_bytes(bytes);
+        if value < limit {
+            return Ok((value % n) as usize);
+        }
+    }
+}
+
+/// random version 4 uuid
+pub fn generate_uuid() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    random_bytes(&mut bytes)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crd::BackendType;
+    use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+    use kube::core::ObjectMeta;
+    use rsa::traits::PublicKeyParts;
+
+    fn generator_spec(kind: GeneratorKind) -> GeneratorSpec {
+        GeneratorSpec {
+            kind,
+            length: None,
+            bits: None,
+            charset: None,
+            classes: None,
+            rotation: None,
+        }
+    }
+
+    fn generator_backend(spec: GeneratorSpec) -> Backend {
+        serde_json::from_value(serde_json::json!({
+            "backend": "Generator",
+            "data": [{ "value": "password", "generator": spec }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate_password() {
+        let password = generate_password(16, &None, &None).unwrap();
+        assert_eq!(password.chars().count(), 16);
+        for class in [LOWERCASE, UPPERCASE, DIGITS, SYMBOLS] {
+            assert!(password.chars().any(|c| class.contains(c)));
+        }
+
+        let password = generate_password(
+            12,
+            &Some("abc123".to_string()),
+            &Some(vec![CharacterClass::Digits]),
+        )
+        .unwrap();
+        assert!(password.chars().all(|c| "abc123".contains(c)));
+        assert!(password.chars().any(|c| DIGITS.contains(c)));
+
+        assert!(generate_password(
+            12,
+            &Some("abc".to_string()),
+            &Some(vec![CharacterClass::Digits])
+        )
+        .is_err());
+        assert!(generate_password(3, &None, &None).is_err());
+    }
+
+    #[test]
+    fn test_generate_uuid() {
+        let uuid = generate_uuid().unwrap();
+
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert!("89ab".contains(&uuid[19..20]));
+        assert_ne!(uuid, generate_uuid().unwrap());
+    }
+
+    #[test]
+    fn test_generate_keypairs() {
+        let data =
+            generate_secret_data("signing", &generator_spec(GeneratorKind::Ed25519)).unwrap();
+        let private_key =
+            SigningKey::from_pkcs8_pem(std::str::from_utf8(&data["signing"].0).unwrap()).unwrap();
+        let public_key = ed25519_dalek::VerifyingKey::from_public_key_pem(
+            std::str::from_utf8(&data["signing.pub"].0).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(private_key.verifying_key(), public_key);
+
+        let spec = GeneratorSpec {
+            bits: Some(2048),
+            ..generator_spec(GeneratorKind::RSA)
+        };
+        let data = generate_secret_data("tls", &spec).unwrap();
+        let rsa =
+            RsaPrivateKey::from_pkcs8_pem(std::str::from_utf8(&data["tls"].0).unwrap()).unwrap();
+        assert_eq!(rsa.size() * 8, 2048);
+
+        let spec = GeneratorSpec {
+            length: Some(64),
+            ..generator_spec(GeneratorKind::Bytes)
+        };
+        assert_eq!(
+            generate_secret_data("seed", &spec).unwrap()["seed"].0.len(),
+            64
+        );
+    }
+
+    #[test]
+    fn test_generate_limits() {
+        for bits in [1024, 2047, 8192] {
+            let spec = GeneratorSpec {
+                bits: Some(bits),
+                ..generator_spec(GeneratorKind::RSA)
+            };
+            assert!(generate_secret_data("tls", &spec).is_err());
+        }
+
+        let spec = GeneratorSpec {
+            length: Some(MAX_LENGTH + 1),
+            ..generator_spec(GeneratorKind::Bytes)
+        };
+        assert!(generate_secret_data("seed", &spec).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_existing_values_are_kept_until_rotation() {
+        let existing = Secret {
+            metadata: ObjectMeta {
+                annotations: Some(BTreeMap::from([(
+                    rotation_annotation("password"),
+                    "1".to_string(),
+                )])),
+                ..ObjectMeta::default()
+            },
+            data: Some(BTreeMap::from([(
+                "password".to_string(),
+                ByteString(b"kept".to_vec()),
+            )])),
+            ..Secret::default()
+        };
+
+        let backend = generator_backend(generator_spec(GeneratorKind::Password));
+        assert_eq!(backend.backend, BackendType::Generator);
+
+        let data = Generator::from_backend(&backend)
+            .with_existing(Some(&existing))
+            .get_value()
+            .await
+            .data;
+        assert_eq!(data["password"].0, b"kept");
+
+        let data = Generator::from_backend(&backend).get_value().await.data;
+        assert_eq!(data["password"].0.len(), 32);
+
+        let backend = generator_backend(GeneratorSpec {
+            rotation: Some("1".to_string()),
+            ..generator_spec(GeneratorKind::Password)
+        });
+        let data = Generator::from_backend(&backend)
+            .with_existing(Some(&existing))
+            .get_value()
+            .await
+            .data;
+        assert_eq!(data["password"].0, b"kept");
+
+        let backend = generator_backend(GeneratorSpec {
+            rotation: Some("2".to_string()),
+            ..generator_spec(GeneratorKind::Password)
+        });
+        let data = Generator::from_backend(&backend)
+            .with_existing(Some(&existing))
+            .get_value()
+            .await
+            .data;
+        assert_ne!(data["password"].0, b"kept");
+        assert_eq!(
+            get_rotation_annotations(&backend),
+            BTreeMap::from([(rotation_annotation("password"), "2".to_string())])
+        );
+    }
+    #[tokio::test]
+    async fn test_existing_values_are_looked_up_under_the_rewritten_keys() {
+        let existing = Secret {
+            data: Some(BTreeMap::from([
+                ("DB_KEY".to_string(), ByteString(b"kept".to_vec())),
+                ("DB_KEY_PUB".to_string(), ByteString(b"kept.pub".to_vec())),
+            ])),
+            ..Secret::default()
+        };
+
+        let mut backend: Backend = serde_json::from_value(serde_json::json!({
+            "backend": "Generator",
+            "data": [{ "value": "key", "generator": generator_spec(GeneratorKind::Ed25519) }]
+        }))
+        .unwrap();
+        backend.rewrite = Some(vec![KeyRewrite {
+            case: Some(crd::KeyCase::UpperSnakeCase),
+            prefix: Some("DB_".to_string()),
+            ..KeyRewrite::default()
+        }]);
+
+        let data = Generator::from_backend(&backend)
+            .with_existing(Some(&existing))
+            .get_value()
+            .await
+            .data;
+        assert_eq!(data["key"].0, b"kept");
+        assert_eq!(data["key.pub"].0, b"kept.pub");
+    }
+    #[tokio::test]
+    async fn test_decoding_strategy_is_rejected() {
+        let backend: Backend = serde_json::from_value(serde_json::json!({
+            "backend": "Generator",
+            "data": [{
+                "value": "password",
+                "generator": generator_spec(GeneratorKind::Password),
+                "decoding_strategy": "Base64"
+            }]
+        }))
+        .unwrap();
+
+        assert!(Generator::from_backend(&backend)
+            .get_value()
+            .await
+            .data
+            .is_empty());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::concurrent::resolve_entries;
+use crate::http_common::{
+    get_allowed_http_hosts, get_http_client, resolve_http_url, ResolvedHttpRequest,
+};
+use crate::registry::Provider;
+use crate::secret_ref::{get_backend_token, get_secret_ref_value};
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, HttpRequest, RemoteData, RemoteValue, SecretData, SecretKeyReference};
+
+use anyhow::{anyhow, Result};
+use kube::Client;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use utils::value::get_secret_data;
+
+#[derive(Clone)]
+pub struct Http {
+    data: Vec<SecretData>,
+    request: HttpRequest,
+    token_secret_ref: Option<SecretKeyReference>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+}
+
+#[async_trait]
+impl RemoteValue for Http {
+    fn from_backend(backend: &Backend) -> Http {
+        Http {
+            data: backend.data.clone(),
+            request: backend.http.clone().unwrap_or_default(),
+            token_secret_ref: backend.token_secret_ref.clone(),
+            namespace: None,
+            client: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let request = match self.resolve_request().await {
+            Ok(request) => request,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let request = &request;
+        let allowed_hosts = &get_allowed_http_hosts();
+
+        resolve_entries(&self.data, |secret_data| async move {
+            let http_data = get_http_value(
+                secret_data.value.clone(),
+                request.clone(),
+                allowed_hosts.clone(),
+            )
+            .await;
+
+            match http_data {
+                Ok(http_data) => get_secret_data(secret_data, &http_data),
+                Err(err) => {
+                    log::error!("{err}");
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// registers the http backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Http", |backend, context| {
+        Box::new(
+            Http::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }).with_fields(&["http", "token_secret_ref"]));
+}
+
+impl Http {
+    /// namespace the header, token and password secrets are read from
+    pub fn in_namespace(mut self, namespace: &str) -> Http {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> Http {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// read every secret referenced by the request
+    async fn resolve_request(&self) -> Result<ResolvedHttpRequest> {
+        let mut headers = Vec::new();
+        for header in self.request.headers.iter() {
+            let value = match (&header.value, &header.value_from) {
+                (_, Some(value_from)) => self.get_secret_ref_value(value_from).await?,
+                (Some(value), None) => value.to_owned(),
+                (None, None) => return Err(anyhow!("no value for the header {}", header.name)),
+            };
+            headers.push((header.name.clone(), value));
+        }
+
+        let basic_auth = match &self.request.basic_auth {
+            Some(basic_auth) => Some((
+                basic_auth.username.clone(),
+                self.get_secret_ref_value(&basic_auth.password_secret_ref)
+                    .await?,
+            )),
+            None => None,
+        };
+
+        Ok(ResolvedHttpRequest {
+            method: self.request.method.clone().unwrap_or_default(),
+            body: self.request.body.clone(),
+            headers,
+            bearer_token: get_backend_token(
+                &self.token_secret_ref,
+                &self.namespace,
+                &self.client,
+                "HTTP_TOKEN",
+            )
+            .await?,
+            basic_auth,
+            ca_bundle: self.request.ca_bundle.clone(),
+        })
+    }
+
+    async fn get_secret_ref_value(&self, secret_ref: &SecretKeyReference) -> Result<String> {
+        let (Some(namespace), Some(client)) = (&self.namespace, &self.client) else {
+            return Err(anyhow!(
+                "no namespace or client to read the secret {}",
+                secret_ref.name
+            ));
+        };
+
+        get_secret_ref_value(client, namespace, secret_ref).await
+    }
+}
+
+/// sha256 of the request, the cache keeps it instead of the tokens and passwords of the request
+pub fn get_http_cache_key(
+    url: &str,
+    request: &ResolvedHttpRequest,
+    allowed_hosts: &[String],
+) -> Vec<u8> {
+    struct Sha256Hasher(Sha256);
+
+    impl Hasher for Sha256Hasher {
+        fn finish(&self) -> u64 {
+            unreachable!("the digest is read with finalize")
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.update(bytes);
+        }
+    }
+
+    let mut hasher = Sha256Hasher(Sha256::new());
+    (url, request, allowed_hosts).hash(&mut hasher);
+    hasher.0.finalize().to_vec()
+}
+
+/// get the response body of the request, redirects are not followed
+/// Will cache the result for 60s
+#[cached(
+    ttl = 60,
+    key = "Vec<u8>",
+    convert = r#"{ get_http_cache_key(&url, &request, &allowed_hosts) }"#
+)]
+pub async fn get_http_value(
+    url: String,
+    request: ResolvedHttpRequest,
+    allowed_hosts: Vec<String>,
+) -> Result<String> {
+    let resolved = resolve_http_url(&url, &allowed_hosts).await?;
+    let client = get_http_client(&url, &request, &resolved)?;
+
+    let response = client.send().await?;
+    if response.status().is_redirection() {
+        return Err(anyhow!("{url} redirects, redirects are not followed"));
+    }
+    Ok(response.error_for_status()?.text().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use crd::HttpMethod;
+    use serde_json::json;
+
+    fn allowed_hosts() -> Vec<String> {
+        vec!["127.0.0.1".to_string()]
+    }
+
+    fn start_secret_service() -> TestServer {
+        TestServer::start(vec![
+            Route::new(
+                "GET",
+                "/secrets/db",
+                200,
+                json!({ "data": { "password": "hunter2" } }).to_string(),
+            ),
+            Route::new(
+                "POST",
+                "/secrets/query",
+                200,
+                json!({ "token": "issued" }).to_string(),
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_get_http_value_with_headers_and_bearer_auth() {
+        let server = start_secret_service();
+        let request = ResolvedHttpRequest {
+            headers: vec![("X-Team".to_string(), "data".to_string())],
+            bearer_token: Some("service-token".to_string()),
+            ..ResolvedHttpRequest::default()
+        };
+
+        let value = get_http_value(
+            format!("{}/secrets/db", server.url),
+            request,
+            allowed_hosts(),
+        )
+        .await
+        .unwrap();
+        let r_data: SecretData = serde_json::from_str(
+            r#"{"value": "", "key": "password", "is_json_string": true, "remote_path": "data.password"}"#,
+        )
+        .unwrap();
+        let data = get_secret_data(&r_data, &value);
+
+        assert_eq!(data["password"].0, b"hunter2");
+
+        let request = &server.requests()[0];
+        assert_eq!(request.headers["x-team"], "data");
+        assert_eq!(request.headers["authorization"], "Bearer service-token");
+    }
+
+    #[tokio::test]
+    async fn test_post_http_value_with_basic_auth() {
+        let server = start_secret_service();
+        let request = ResolvedHttpRequest {
+            method: HttpMethod::POST,
+            body: Some(r#"{"name": "db"}"#.to_string()),
+            basic_auth: Some(("app".to_string(), "secret".to_string())),
+            ..ResolvedHttpRequest::default()
+        };
+
+        let value = get_http_value(
+            format!("{}/secrets/query", server.url),
+            request,
+            allowed_hosts(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, r#"{"token":"issued"}"#);
+
+        let request = &server.requests()[0];
+        assert_eq!(request.body, r#"{"name": "db"}"#);
+        assert_eq!(request.headers["authorization"], "Basic YXBwOnNlY3JldA==");
+    }
+
+    #[tokio::test]
+    async fn test_http_error_status() {
+        let server = start_secret_service();
+
+        let result = get_http_value(
+            format!("{}/secrets/missing", server.url),
+            ResolvedHttpRequest::default(),
+            allowed_hosts(),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_internal_addresses_are_denied_by_default() {
+        let server = start_secret_service();
+
+        for url in [
+            format!("{}/secrets/db", server.url),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+            "http://[::ffff:169.254.169.254]/".to_string(),
+            "http://[::1]/".to_string(),
+            "http://10.0.0.1/".to_string(),
+            "http://172.16.0.10/".to_string(),
+            "http://192.168.1.1/".to_string(),
+            "http://100.64.0.1/".to_string(),
+            "http://[fd00::1]/".to_string(),
+        ] {
+            let result = get_http_value(url, ResolvedHttpRequest::default(), vec![]).await;
+            assert!(result.is_err());
+        }
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_http_cache_key() {
+        let request = ResolvedHttpRequest {
+            bearer_token: Some("service-token".to_string()),
+            ..ResolvedHttpRequest::default()
+        };
+        let other = ResolvedHttpRequest {
+            bearer_token: Some("other-token".to_string()),
+            ..ResolvedHttpRequest::default()
+        };
+
+        let key = get_http_cache_key("http://secrets.internal", &request, &[]);
+        assert_eq!(key.len(), 32);
+        assert!(!key.windows(13).any(|window| window == b"service-token"));
+        assert_eq!(
+            key,
+            get_http_cache_key("http://secrets.internal", &request.clone(), &[])
+        );
+        assert_ne!(
+            key,
+            get_http_cache_key("http://secrets.internal", &other, &[])
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crd::HttpMethod;
+
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, SocketAddr};
+
+/// http request with the referenced secrets read
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResolvedHttpRequest {
+    pub method: HttpMethod,
+    pub body: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub bearer_token: Option<String>,
+    pub basic_auth: Option<(String, String)>,
+    pub ca_bundle: Option<String>,
+}
+
+/// hosts the http backend may request, comma separated in HTTP_ALLOWED_HOSTS
+/// eg secrets.internal, *.example.com or * for every host
+pub fn get_allowed_http_hosts() -> Vec<String> {
+    std::env::var("HTTP_ALLOWED_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().to_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// without HTTP_ALLOWED_HOSTS every host is allowed
+pub fn is_http_host_allowed(host: &str, allowed_hosts: &[String]) -> bool {
+    allowed_hosts.is_empty()
+        || allowed_hosts.iter().any(|allowed| {
+            allowed == "*"
+                || allowed == host
+                || allowed
+                    .strip_prefix("*.")
+                    .is_some_and(|domain| host.ends_with(&format!(".{domain}")))
+        })
+}
+
+/// loopback, link-local, eg the metadata endpoints of the cloud providers, and private addresses,
+/// eg the pods and services of the cluster, unless the host is listed explicitly in HTTP_ALLOWED_HOSTS
+pub fn is_internal_address(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_private()
+                // shared address space of carrier-grade nat, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_internal_address(&IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // link-local fe80::/10 and unique local fc00::/7
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+            }
+        },
+    }
+}
+
+/// check the host of the url and resolve it, the request is sent to the checked addresses only
+pub async fn resolve_http_url(
+    url: &str,
+    allowed_hosts: &[String],
+) -> Result<(String, Vec<SocketAddr>)> {
+    let parsed = reqwest::Url::parse(url)?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(anyhow!("unsupported scheme of {url}"));
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| anyhow!("no host in {url}"))?
+        .to_lowercase();
+    if !is_http_host_allowed(&host, allowed_hosts) {
+        return Err(anyhow!("{host} is not in HTTP_ALLOWED_HOSTS"));
+    }
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("no port for {url}"))?;
+
+    let lookup = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((lookup, port)).await?.collect();
+    let is_listed = allowed_hosts.iter().any(|allowed| allowed == &host);
+    if !is_listed
+        && addresses
+            .iter()
+            .any(|address| is_internal_address(&address.ip()))
+    {
+        return Err(anyhow!(
+            "{host} resolves to a loopback, link-local or private address, list it in HTTP_ALLOWED_HOSTS to allow it"
+        ));
+    }
+
+    Ok((host, addresses))
+}
+
+/// the host is pinned to its checked addresses, a redirect could point to a host that is not allowed
+pub fn get_http_client(
+    url: &str,
+    request: &ResolvedHttpRequest,
+    (host, addresses): &(String, Vec<SocketAddr>),
+) -> Result<reqwest::RequestBuilder> {
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, addresses);
+    if let Some(ca_bundle) = &request.ca_bundle {
+        let certificates = reqwest::Certificate::from_pem_bundle(ca_bundle.as_bytes())?;
+        if certificates.is_empty() {
+            return Err(anyhow!("no certificate found in the ca bundle"));
+        }
+        builder = builder.tls_certs_merge(certificates);
+    }
+    let client = builder.build()?;
+
+    let mut client = match request.method {
+        HttpMethod::GET => client.get(url),
+        HttpMethod::POST => client
+            .post(url)
+            .body(request.body.clone().unwrap_or_default()),
+    };
+
+    for (name, value) in request.headers.iter() {
+        client = client.header(name, value);
+    }
+    if let Some(token) = &request.bearer_token {
+        client = client.bearer_auth(token);
+    }
+    if let Some((username, password)) = &request.basic_auth {
+        client = client.basic_auth(username, Some(password));
+    }
+
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_ca_bundle() {
+        let request = ResolvedHttpRequest {
+            ca_bundle: Some("not a certificate".to_string()),
+            ..ResolvedHttpRequest::default()
+        };
+
+        let resolved = ("localhost".to_string(), vec![]);
+
+        assert!(get_http_client("https://localhost", &request, &resolved).is_err());
+    }
+
+    #[test]
+    fn test_allowed_http_hosts() {
+        let allowed_hosts = vec!["secrets.internal".to_string(), "*.example.com".to_string()];
+
+        assert!(is_http_host_allowed("secrets.internal", &allowed_hosts));
+        assert!(is_http_host_allowed("vault.example.com", &allowed_hosts));
+        assert!(!is_http_host_allowed("example.com", &allowed_hosts));
+        assert!(!is_http_host_allowed("evil.com", &allowed_hosts));
+        assert!(is_http_host_allowed("evil.com", &[]));
+        assert!(is_http_host_allowed("evil.com", &["*".to_string()]));
+    }
+}
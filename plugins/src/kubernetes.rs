@@ -0,0 +1,326 @@
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
+use async_trait::async_trait;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use anyhow::Result;
+use k8s_openapi::api::core::v1::{ConfigMap as K8sConfigMap, Secret};
+use k8s_openapi::ByteString;
+use kube::{Api, Client};
+use std::collections::BTreeMap;
+
+/// secrets and config maps with this label set to true are watched, the rsecrets reading them
+/// are reconciled as soon as they change
+pub const SOURCE_LABEL: &str = "rsecrets.jerry153fish.com/source";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    Secret,
+    ConfigMap,
+}
+
+#[derive(Clone)]
+pub struct KubernetesSecret {
+    data: Vec<SecretData>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+}
+
+#[async_trait]
+impl RemoteValue for KubernetesSecret {
+    fn from_backend(backend: &Backend) -> KubernetesSecret {
+        KubernetesSecret {
+            data: backend.data.clone(),
+            namespace: None,
+            client: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        get_source_value(
+            &self.data,
+            &self.namespace,
+            &self.client,
+            SourceKind::Secret,
+        )
+        .await
+    }
+}
+
+/// registers the kubernetes secret and config map backends in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("KubernetesSecret", |backend, context| {
+        Box::new(
+            KubernetesSecret::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }));
+    providers.push(Provider::new("ConfigMap", |backend, context| {
+        Box::new(
+            ConfigMap::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }));
+}
+
+impl KubernetesSecret {
+    /// namespace of the rsecret, the default namespace of the source secrets
+    pub fn in_namespace(mut self, namespace: &str) -> KubernetesSecret {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> KubernetesSecret {
+        self.client = Some(client.clone());
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct ConfigMap {
+    data: Vec<SecretData>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+}
+
+#[async_trait]
+impl RemoteValue for ConfigMap {
+    fn from_backend(backend: &Backend) -> ConfigMap {
+        ConfigMap {
+            data: backend.data.clone(),
+            namespace: None,
+            client: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        get_source_value(
+            &self.data,
+            &self.namespace,
+            &self.client,
+            SourceKind::ConfigMap,
+        )
+        .await
+    }
+}
+
+
+impl ConfigMap {
+    /// namespace of the rsecret, the default namespace of the source config maps
+    pub fn in_namespace(mut self, namespace: &str) -> ConfigMap {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> ConfigMap {
+        self.client = Some(client.clone());
+        self
+    }
+}
+
+async fn get_source_value(
+    data: &[SecretData],
+    namespace: &Option<String>,
+    client: &Option<Client>,
+    kind: SourceKind,
+) -> RemoteData {
+    let (Some(namespace), Some(client)) = (namespace, client) else {
+        log::error!("no namespace or client to read the {kind:?} sources");
+        return RemoteData::default();
+    };
+    let allowed_namespaces = &get_allowed_source_namespaces();
+
+    resolve_entries(data, |secret_data| async move {
+        let (source_namespace, name) = parse_source_ref(&secret_data.value, namespace);
+        if !is_source_namespace_allowed(&source_namespace, namespace, allowed_namespaces) {
+            log::error!(
+                "{kind:?} {source_namespace}/{name} is not in SOURCE_NAMESPACES, skipping it"
+            );
+            return BTreeMap::new();
+        }
+
+        match read_source_data(client, kind, &source_namespace, &name).await {
+            Ok(source) => get_source_secret_data(secret_data, &source),
+            Err(err) => {
+                log::error!("failed to read {kind:?} {source_namespace}/{name}: {err}");
+                BTreeMap::new()
+            }
+        }
+    })
+    .await
+}
+
+/// value is name, or namespace/name to read from another namespace
+pub fn parse_source_ref(value: &str, namespace: &str) -> (String, String) {
+    match value.split_once('/') {
+        Some((source_namespace, name)) => (source_namespace.to_owned(), name.to_owned()),
+        None => (namespace.to_owned(), value.to_owned()),
+    }
+}
+
+/// namespaces other rsecrets may read sources from, comma separated in SOURCE_NAMESPACES
+pub fn get_allowed_source_namespaces() -> Vec<String> {
+    std::env::var("SOURCE_NAMESPACES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|namespace| namespace.trim().to_owned())
+        .filter(|namespace| !namespace.is_empty())
+        .collect()
+}
+
+/// sources in the namespace of the rsecret are always allowed, * allows every namespace
+pub fn is_source_namespace_allowed(
+    source_namespace: &str,
+    namespace: &str,
+    allowed_namespaces: &[String],
+) -> bool {
+    source_namespace == namespace
+        || allowed_namespaces
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == source_namespace)
+}
+
+/// read the data of the secret, or the data and binary data of the config map
+pub async fn read_source_data(
+    client: &Client,
+    kind: SourceKind,
+    namespace: &str,
+    name: &str,
+) -> Result<BTreeMap<String, Vec<u8>>> {
+    match kind {
+        SourceKind::Secret => {
+            let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+            let secret = api.get(name).await?;
+
+            Ok(secret
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, value.0))
+                .collect())
+        }
+        SourceKind::ConfigMap => {
+            let api: Api<K8sConfigMap> = Api::namespaced(client.clone(), namespace);
+            let config_map = api.get(name).await?;
+
+            let data = config_map
+                .data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, value.into_bytes()));
+            let binary_data = config_map
+                .binary_data
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(key, value)| (key, value.0));
+
+            Ok(data.chain(binary_data).collect())
+        }
+    }
+}
+
+/// every key of the source, or the remote_path key of the source as key, defaulting to remote_path
+pub fn get_source_secret_data(
+    secret_data: &SecretData,
+    source: &BTreeMap<String, Vec<u8>>,
+) -> BTreeMap<String, ByteString> {
+    let Some(remote_path) = &secret_data.remote_path else {
+        return source
+            .iter()
+            .map(|(key, value)| (key.to_owned(), ByteString(value.to_owned())))
+            .collect();
+    };
+
+    match source.get(remote_path) {
+        Some(value) => {
+            let key = secret_data.key.clone().unwrap_or(remote_path.to_owned());
+            BTreeMap::from([(key, ByteString(value.to_owned()))])
+        }
+        None => {
+            log::error!("no key {remote_path} found in {}", secret_data.value);
+            BTreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source_ref() {
+        assert_eq!(
+            parse_source_ref("platform/ca-bundle", "apps"),
+            ("platform".to_string(), "ca-bundle".to_string())
+        );
+        assert_eq!(
+            parse_source_ref("ca-bundle", "apps"),
+            ("apps".to_string(), "ca-bundle".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_source_namespace_allowed() {
+        let allowed = vec!["platform".to_string()];
+
+        assert!(is_source_namespace_allowed("apps", "apps", &[]));
+        assert!(is_source_namespace_allowed("platform", "apps", &allowed));
+        assert!(!is_source_namespace_allowed(
+            "kube-system",
+            "apps",
+            &allowed
+        ));
+        assert!(is_source_namespace_allowed(
+            "kube-system",
+            "apps",
+            &["*".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_get_source_secret_data() {
+        let source = BTreeMap::from([
+            (
+                "ca.crt".to_string(),
+                b"-----BEGIN CERTIFICATE-----".to_vec(),
+            ),
+            ("config".to_string(), br#"{"mode": "dark"}"#.to_vec()),
+        ]);
+
+        let all = get_source_secret_data(
+            &SecretData {
+                value: "platform/ca-bundle".to_string(),
+                ..SecretData::default()
+            },
+            &source,
+        );
+        assert_eq!(all.len(), 2);
+
+        let renamed = get_source_secret_data(
+            &SecretData {
+                value: "platform/ca-bundle".to_string(),
+                key: Some("platform-ca.crt".to_string()),
+                remote_path: Some("ca.crt".to_string()),
+                ..SecretData::default()
+            },
+            &source,
+        );
+        assert_eq!(renamed["platform-ca.crt"].0, b"-----BEGIN CERTIFICATE-----");
+
+        let missing = get_source_secret_data(
+            &SecretData {
+                value: "platform/ca-bundle".to_string(),
+                remote_path: Some("tls.key".to_string()),
+                ..SecretData::default()
+            },
+            &source,
+        );
+        assert!(missing.is_empty());
+    }
+}
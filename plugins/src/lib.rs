@@ -1,7 +1,64 @@
-pub mod aws_cfn;
 pub mod aws_common;
-pub mod aws_secret_manager;
-pub mod aws_ssm;
-pub mod plaintext;
-pub mod pulumi;
-pub mod vault;
+pub mod concurrent;
+#[cfg(any(feature = "http", feature = "terraform"))]
+pub mod http_common;
+#[cfg(feature = "pulumi")]
+pub mod pulumi_state;
+pub mod registry;
+pub mod secret_ref;
+
+/// declares the modules of the providers, every module adds its providers to the registry
+/// in its register function, and is only compiled in, and registered, under its cargo feature
+macro_rules! provider_modules {
+    ($($(#[$attr:meta])* $module:ident,)*) => {
+        $($(#[$attr])* pub mod $module;)*
+
+        /// the providers of every provider module compiled in
+        fn register_providers(providers: &mut Vec<registry::Provider>) {
+            $($(#[$attr])* $module::register(providers);)*
+        }
+    };
+}
+
+provider_modules! {
+    #[cfg(feature = "aws")]
+    aws_cfn,
+    #[cfg(feature = "aws")]
+    aws_ecr,
+    #[cfg(feature = "aws")]
+    aws_kms,
+    #[cfg(feature = "aws")]
+    aws_s3,
+    #[cfg(feature = "aws")]
+    aws_secret_manager,
+    #[cfg(feature = "aws")]
+    aws_ssm,
+    #[cfg(feature = "azure")]
+    azure_key_vault,
+    #[cfg(feature = "consul")]
+    consul,
+    #[cfg(feature = "external")]
+    external,
+    #[cfg(feature = "gcp")]
+    gcp_secret_manager,
+    generator,
+    #[cfg(feature = "http")]
+    http,
+    kubernetes,
+    #[cfg(feature = "onepassword")]
+    onepassword,
+    plaintext,
+    #[cfg(feature = "pulumi")]
+    pulumi,
+    #[cfg(feature = "sops")]
+    sops,
+    #[cfg(feature = "terraform")]
+    terraform,
+    #[cfg(feature = "vault")]
+    vault,
+}
+
+// only used by the tests of the providers talking http
+#[cfg(test)]
+#[allow(dead_code)]
+mod test_server;
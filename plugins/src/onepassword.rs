@@ -0,0 +1,424 @@
+use crate::concurrent::resolve_entries_data;
+use crate::registry::Provider;
+use crate::secret_ref::get_backend_token;
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, EntryConflict, RemoteData, RemoteValue, SecretData, SecretKeyReference};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::ByteString;
+use kube::Client;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use utils::rewrite::is_valid_secret_key;
+use utils::value::get_secret_bytes_data;
+
+#[derive(Clone)]
+pub struct OnePassword {
+    data: Vec<SecretData>,
+    token_secret_ref: Option<SecretKeyReference>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+}
+
+#[async_trait]
+impl RemoteValue for OnePassword {
+    fn from_backend(backend: &Backend) -> OnePassword {
+        OnePassword {
+            data: backend.data.clone(),
+            token_secret_ref: backend.token_secret_ref.clone(),
+            namespace: None,
+            client: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let connect = match self.get_connect().await {
+            Ok(connect) => connect,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let connect = &connect;
+
+        resolve_entries_data(&self.data, |secret_data| async move {
+            let item = match parse_item_path(&secret_data.value) {
+                Ok((vault, title)) => get_onepassword_item(connect.clone(), vault, title).await,
+                Err(err) => Err(err),
+            };
+
+            let item = match item {
+                Ok(item) => item,
+                Err(err) => {
+                    log::error!("{err}");
+                    return RemoteData::default();
+                }
+            };
+
+            // specific field for 1-1 mapping k8s secret key
+            if let (Some(_), Some(remote_path)) = (&secret_data.key, &secret_data.remote_path) {
+                let data = match item.iter().find(|(label, _)| label == remote_path) {
+                    Some((_, value)) => get_secret_bytes_data(
+                        &SecretData {
+                            remote_path: None,
+                            ..secret_data.clone()
+                        },
+                        value,
+                    ),
+                    None => {
+                        log::error!(
+                            "no field or file {remote_path} found in {}",
+                            secret_data.value
+                        );
+                        BTreeMap::new()
+                    }
+                };
+                data.into()
+            } else {
+                // insert every field and file of the item into k8s secret data
+                item_to_secret_data(&item)
+            }
+        })
+        .await
+    }
+}
+
+/// registers the 1password backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("OnePassword", |backend, context| {
+        Box::new(
+            OnePassword::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }).with_fields(&["token_secret_ref"]));
+}
+
+impl OnePassword {
+    /// namespace the token secret is read from
+    pub fn in_namespace(mut self, namespace: &str) -> OnePassword {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> OnePassword {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// connect server from OP_CONNECT_HOST, token from the secret reference or OP_CONNECT_TOKEN
+    async fn get_connect(&self) -> Result<OnePasswordConnect> {
+        let host = std::env::var("OP_CONNECT_HOST")
+            .map_err(|_| anyhow!("OP_CONNECT_HOST is required for 1password"))?;
+        let token = get_backend_token(
+            &self.token_secret_ref,
+            &self.namespace,
+            &self.client,
+            "OP_CONNECT_TOKEN",
+        )
+        .await?
+        .ok_or_else(|| anyhow!("token_secret_ref or OP_CONNECT_TOKEN is required"))?;
+
+        Ok(OnePasswordConnect { host, token })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OnePasswordConnect {
+    pub host: String,
+    pub token: String,
+}
+
+impl OnePasswordConnect {
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        let url = format!("{}/v1/{path}", self.host.trim_end_matches('/'));
+
+        Ok(reqwest::Client::new()
+            .get(url)
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?)
+    }
+
+    /// id of the only resource at the path matching the filter
+    async fn find_id(&self, path: &str, filter: &str, name: &str) -> Result<String> {
+        let filter = format!("{filter} eq \"{name}\"");
+        let url = format!("{}/v1/{path}", self.host.trim_end_matches('/'));
+        let found: Vec<OnePasswordRef> = reqwest::Client::new()
+            .get(url)
+            .query(&[("filter", filter)])
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match found.as_slice() {
+            [found] => Ok(found.id.clone()),
+            [] => Err(anyhow!("no 1password {path} found with {name}")),
+            _ => Err(anyhow!("more than one 1password {path} found with {name}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct OnePasswordRef {
+    id: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OnePasswordItem {
+    #[serde(default)]
+    pub fields: Vec<OnePasswordField>,
+    #[serde(default)]
+    pub files: Vec<OnePasswordFile>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OnePasswordField {
+    pub id: String,
+    pub label: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OnePasswordFile {
+    pub id: String,
+    pub name: String,
+}
+
+/// value is {vault}/{item title}, the title may contain /
+pub fn parse_item_path(value: &str) -> Result<(String, String)> {
+    value
+        .split_once('/')
+        .filter(|(vault, title)| !vault.is_empty() && !title.is_empty())
+        .map(|(vault, title)| (vault.to_owned(), title.to_owned()))
+        .ok_or_else(|| anyhow!("invalid 1password item {value}, expected vault/item"))
+}
+
+/// get the fields and the file attachments of the item with their field label and file name,
+/// in the order of the item, labels are not unique across the sections of an item
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn get_onepassword_item(
+    connect: OnePasswordConnect,
+    vault: String,
+    title: String,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let vault_id = connect.find_id("vaults", "name", &vault).await?;
+    let items_path = format!("vaults/{vault_id}/items");
+    let item_id = connect.find_id(&items_path, "title", &title).await?;
+    let item: OnePasswordItem = connect
+        .get(&format!("{items_path}/{item_id}"))
+        .await?
+        .json()
+        .await?;
+
+    let mut values = Vec::new();
+    for field in item.fields {
+        if let Some(value) = field.value {
+            values.push((field.label.unwrap_or(field.id), value.into_bytes()));
+        }
+    }
+    for file in item.files {
+        let content = connect
+            .get(&format!("{items_path}/{item_id}/files/{}/content", file.id))
+            .await?
+            .bytes()
+            .await?;
+        values.push((file.name, content.to_vec()));
+    }
+
+    Ok(values)
+}
+
+/// every field and file of the item keyed by its label made a valid secret key, eg one-time password
+/// becomes one-time_password, the first field of a key wins and the others are reported as conflicts
+pub fn item_to_secret_data(item: &[(String, Vec<u8>)]) -> RemoteData {
+    let mut data = BTreeMap::new();
+    let mut duplicates = BTreeSet::new();
+
+    for (label, value) in item {
+        let key = to_secret_key(label);
+        if !is_valid_secret_key(&key) {
+            log::error!("1password field {label:?} is not a valid secret key, skipping it");
+            continue;
+        }
+        if data.contains_key(&key) {
+            duplicates.insert(key);
+            continue;
+        }
+        data.insert(key, ByteString(value.clone()));
+    }
+
+    RemoteData {
+        data,
+        conflicts: duplicates
+            .into_iter()
+            .map(|key| EntryConflict {
+                key,
+                entries: vec![],
+            })
+            .collect(),
+    }
+}
+
+/// the characters not allowed in secret keys are replaced by _
+fn to_secret_key(label: &str) -> String {
+    label
+        .trim()
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{Route, TestServer};
+    use serde_json::json;
+
+    fn start_connect() -> TestServer {
+        TestServer::start(vec![
+            Route::new(
+                "GET",
+                "/v1/vaults",
+                200,
+                json!([{ "id": "vault-id", "name": "Infra" }]).to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/v1/vaults/vault-id/items",
+                200,
+                json!([{ "id": "item-id", "title": "Database" }]).to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/v1/vaults/vault-id/items/item-id",
+                200,
+                json!({
+                    "id": "item-id",
+                    "title": "Database",
+                    "fields": [
+                        { "id": "username", "label": "username", "value": "app" },
+                        { "id": "password", "label": "password", "value": "hunter2" },
+                        { "id": "notesPlain", "label": "notesPlain" }
+                    ],
+                    "files": [
+                        { "id": "file-id", "name": "ca.pem", "content_path": "/v1/vaults/vault-id/items/item-id/files/file-id/content" }
+                    ]
+                })
+                .to_string(),
+            ),
+            Route::new(
+                "GET",
+                "/v1/vaults/vault-id/items/item-id/files/file-id/content",
+                200,
+                "-----BEGIN CERTIFICATE-----",
+            ),
+        ])
+    }
+
+    fn connect(server: &TestServer) -> OnePasswordConnect {
+        OnePasswordConnect {
+            host: server.url.clone(),
+            token: "connect-token".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_item_path() {
+        assert_eq!(
+            parse_item_path("Infra/Database").unwrap(),
+            ("Infra".to_string(), "Database".to_string())
+        );
+        assert_eq!(
+            parse_item_path("Infra/db/primary").unwrap(),
+            ("Infra".to_string(), "db/primary".to_string())
+        );
+        assert!(parse_item_path("Database").is_err());
+        assert!(parse_item_path("Infra/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_onepassword_item() {
+        let server = start_connect();
+
+        let item = get_onepassword_item(
+            connect(&server),
+            "Infra".to_string(),
+            "Database".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(item.len(), 3);
+        let data = item_to_secret_data(&item).data;
+        assert_eq!(data["username"].0, b"app");
+        assert_eq!(data["password"].0, b"hunter2");
+        assert_eq!(data["ca.pem"].0, b"-----BEGIN CERTIFICATE-----");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/vaults?filter=name+eq+%22Infra%22");
+        assert_eq!(requests[0].headers["authorization"], "Bearer connect-token");
+        assert_eq!(
+            requests[1].path,
+            "/v1/vaults/vault-id/items?filter=title+eq+%22Database%22"
+        );
+    }
+
+    #[test]
+    fn test_item_to_secret_data() {
+        let item = vec![
+            ("one-time password".to_string(), b"otpauth://".to_vec()),
+            ("password".to_string(), b"first".to_vec()),
+            // a field of another section named like the first one
+            ("password".to_string(), b"second".to_vec()),
+            ("???".to_string(), b"kept as ___".to_vec()),
+            ("".to_string(), b"dropped".to_vec()),
+        ];
+
+        let remote = item_to_secret_data(&item);
+        assert_eq!(remote.data.len(), 3);
+        assert_eq!(remote.data["one-time_password"].0, b"otpauth://");
+        assert_eq!(remote.data["password"].0, b"first");
+        assert_eq!(remote.data["___"].0, b"kept as ___");
+        assert_eq!(
+            remote.conflicts,
+            vec![EntryConflict {
+                key: "password".to_string(),
+                entries: vec![],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_onepassword_item() {
+        let server = TestServer::start(vec![
+            Route::new(
+                "GET",
+                "/v1/vaults",
+                200,
+                json!([{ "id": "vault-id", "name": "Infra" }]).to_string(),
+            ),
+            Route::new("GET", "/v1/vaults/vault-id/items", 200, "[]"),
+        ]);
+
+        let result =
+            get_onepassword_item(connect(&server), "Infra".to_string(), "Missing".to_string())
+                .await;
+
+        assert!(result.is_err());
+    }
+}
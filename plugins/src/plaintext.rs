@@ -1,9 +1,12 @@
+use crate::registry::Provider;
 use async_trait::async_trait;
-use crd::{Backend, RemoteValue, SecretData};
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
 
 use k8s_openapi::ByteString;
 use std::collections::BTreeMap;
 
+use crate::concurrent::resolve_entries;
+
 #[derive(Clone, Debug)]
 pub struct PlainText {
     data: Vec<SecretData>,
@@ -17,24 +20,27 @@ impl RemoteValue for PlainText {
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
-
-        for secret_data in self.data.iter() {
-            if secret_data.key.is_some() {
-                let key = secret_data.key.clone().unwrap();
-
-                secrets.insert(
-                    key,
-                    ByteString(secret_data.value.clone().as_bytes().to_vec()),
-                );
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
+            match &secret_data.key {
+                Some(key) => BTreeMap::from([(
+                    key.to_owned(),
+                    ByteString(secret_data.value.as_bytes().to_vec()),
+                )]),
+                None => BTreeMap::new(),
             }
-        }
-
-        secrets
+        })
+        .await
     }
 }
 
+/// registers the plaintext backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Plaintext", |backend, _| {
+        Box::new(PlainText::from_backend(backend))
+    }));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
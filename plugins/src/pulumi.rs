@@ -3,18 +3,33 @@ use std::collections::BTreeMap;
 
 use cached::macros::cached;
 use k8s_openapi::ByteString;
+use kube::Client;
 
-use crd::{Backend, RemoteValue, SecretData};
-use json_dotpath::DotPaths;
+use crd::{Backend, PathMode, RemoteData, RemoteValue, SecretData, SecretKeyReference};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
 
-use utils::value::get_secret_data;
+use crate::concurrent::resolve_entries;
+use crate::pulumi_state::{get_pulumi_state_outputs, is_self_managed_state};
+use crate::registry::Provider;
+use crate::secret_ref::get_backend_token;
+use utils::value::{get_document_value, get_secret_data, json_value_to_string};
 
-#[derive(Clone, Debug)]
+const PULUMI_STACK_RESOURCE_TYPE: &str = "pulumi:pulumi:Stack";
+/// property marking a pulumi secret value in a stack export
+const PULUMI_SECRET_SIG_KEY: &str = "4dabf18193072939515e22adb298388d";
+const PULUMI_SECRET_SIG: &str = "1b47061264138c4ac30d75fd1eb44270";
+
+#[derive(Clone)]
 pub struct Pulumi {
     data: Vec<SecretData>,
     token: Option<String>,
+    passphrase_secret_ref: Option<SecretKeyReference>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
 }
 
 #[async_trait]
@@ -23,76 +38,219 @@ impl RemoteValue for Pulumi {
         Pulumi {
             data: backend.data.clone(),
             token: backend.pulumi_token.clone(),
+            passphrase_secret_ref: backend.pulumi_passphrase_secret_ref.clone(),
+            namespace: None,
+            client: None,
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
+    async fn get_value(&self) -> RemoteData {
+        let passphrase = match self.get_passphrase().await {
+            Ok(passphrase) => passphrase,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let passphrase = &passphrase;
 
-        for secret_data in self.data.iter() {
+        resolve_entries(&self.data, |secret_data| async move {
             // specific the output value for 1-1 mapping k8s secret key
             // TODO: support the output value is not dict
-            if secret_data.key.is_some() && secret_data.remote_path.is_some() {
-                let data = get_pulumi_output(
+            let data = if secret_data.key.is_some() && secret_data.remote_path.is_some() {
+                get_pulumi_output(
                     secret_data.value.clone(),
                     secret_data.remote_path.clone().unwrap(),
+                    &secret_data.path_mode.clone().unwrap_or_default(),
                     self.token.clone(),
+                    passphrase.clone(),
                 )
-                .await;
-
-                match data {
-                    Ok(data) => {
-                        let data = get_secret_data(secret_data, &data);
-
-                        secrets = data
-                            .into_iter()
-                            .chain(secrets.clone().into_iter())
-                            .collect();
-                    }
-                    Err(err) => {
-                        log::error!("{err}");
-                    }
-                }
+                .await
+                .map(|data| get_secret_data(secret_data, &data))
             } else {
                 // insert the whole cloudformation outputs into k8s secret data
-                let data = get_pulumi_outputs_as_secret_data(
+                get_pulumi_outputs_as_secret_data(
                     secret_data.value.clone(),
                     self.token.clone(),
+                    passphrase.clone(),
                 )
-                .await;
-
-                match data {
-                    Ok(data) => {
-                        secrets = data
-                            .into_iter()
-                            .chain(secrets.clone().into_iter())
-                            .collect();
-                    }
-                    Err(err) => {
-                        log::error!("{err}");
-                    }
-                }
-            }
-        }
+                .await
+            };
 
-        secrets
+            data.unwrap_or_else(|err| {
+                log::error!("{err}");
+                BTreeMap::new()
+            })
+        })
+        .await
     }
 }
 
+/// registers the pulumi backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Pulumi", |backend, context| {
+        Box::new(
+            Pulumi::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }).with_fields(&["pulumi_token", "pulumi_passphrase_secret_ref"]));
+}
+
+impl Pulumi {
+    /// namespace the passphrase secret is read from
+    pub fn in_namespace(mut self, namespace: &str) -> Pulumi {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the secrets of the rsecret namespace are read with
+    pub fn with_client(mut self, client: &Client) -> Pulumi {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// passphrase from the secret reference, PULUMI_CONFIG_PASSPHRASE otherwise
+    async fn get_passphrase(&self) -> Result<Option<String>> {
+        get_backend_token(
+            &self.passphrase_secret_ref,
+            &self.namespace,
+            &self.client,
+            "PULUMI_CONFIG_PASSPHRASE",
+        )
+        .await
+    }
+}
+
+/// get the outputs of the stack resource, with secret outputs decrypted
+/// path is either a pulumi service stack or a self-managed checkpoint url
+/// Will cache the result for 60s
 #[cached(ttl = 60)]
 pub async fn get_pulumi_outputs(
     path: String,
     pulumi_token: Option<String>,
-) -> Result<serde_json::Value> {
-    let client = get_pulumi_client(path.clone(), pulumi_token)?;
-    let response: serde_json::Value = client.send().await?.json().await?;
+    pulumi_passphrase: Option<String>,
+) -> Result<Value> {
+    if is_self_managed_state(&path) {
+        return get_pulumi_state_outputs(&path, pulumi_passphrase).await;
+    }
+
+    let client = get_pulumi_client(path.clone(), pulumi_token.clone())?;
+    let response: Value = client.send().await?.error_for_status()?.json().await?;
+
+    let deployment = response
+        .get("deployment")
+        .ok_or_else(|| anyhow!("no deployment found in the pulumi stack export"))?;
+    let outputs = get_stack_resource_outputs(deployment)?;
+
+    let mut plaintexts = BTreeMap::new();
+    for ciphertext in collect_secret_ciphertexts(&outputs) {
+        let plaintext = decrypt_pulumi_secret(&path, pulumi_token.clone(), &ciphertext).await?;
+        plaintexts.insert(ciphertext, plaintext);
+    }
+
+    reveal_secrets(outputs, &plaintexts)
+}
+
+/// find the outputs of the pulumi:pulumi:Stack resource in a deployment
+pub fn get_stack_resource_outputs(deployment: &Value) -> Result<Value> {
+    let resources = deployment
+        .get("resources")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("no resources found in the pulumi deployment"))?;
+
+    let stack = resources
+        .iter()
+        .find(|resource| resource["type"] == PULUMI_STACK_RESOURCE_TYPE)
+        .ok_or_else(|| anyhow!("no {PULUMI_STACK_RESOURCE_TYPE} resource found"))?;
+
+    Ok(stack.get("outputs").cloned().unwrap_or_else(|| json!({})))
+}
+
+fn is_secret(value: &Value) -> bool {
+    value.get(PULUMI_SECRET_SIG_KEY).and_then(Value::as_str) == Some(PULUMI_SECRET_SIG)
+}
+
+/// collect the ciphertexts of all the secret values nested in the outputs
+pub fn collect_secret_ciphertexts(value: &Value) -> Vec<String> {
+    let mut ciphertexts = Vec::new();
+    let mut pending = vec![value];
+
+    while let Some(value) = pending.pop() {
+        if is_secret(value) {
+            if let Some(ciphertext) = value.get("ciphertext").and_then(Value::as_str) {
+                ciphertexts.push(ciphertext.to_owned());
+            }
+            continue;
+        }
+
+        match value {
+            Value::Array(items) => pending.extend(items.iter()),
+            Value::Object(fields) => pending.extend(fields.values()),
+            _ => {}
+        }
+    }
+
+    ciphertexts
+}
+
+/// replace the secret values in the outputs with their decrypted values
+pub fn reveal_secrets(value: Value, plaintexts: &BTreeMap<String, Value>) -> Result<Value> {
+    if is_secret(&value) {
+        if let Some(plaintext) = value.get("plaintext").and_then(Value::as_str) {
+            return Ok(serde_json::from_str(plaintext)?);
+        }
+
+        let ciphertext = value
+            .get("ciphertext")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("pulumi secret has no ciphertext"))?;
+
+        return plaintexts
+            .get(ciphertext)
+            .cloned()
+            .ok_or_else(|| anyhow!("pulumi secret has not been decrypted"));
+    }
+
+    match value {
+        Value::Array(items) => Ok(Value::Array(
+            items
+                .into_iter()
+                .map(|item| reveal_secrets(item, plaintexts))
+                .collect::<Result<_>>()?,
+        )),
+        Value::Object(fields) => Ok(Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, item)| Ok((key, reveal_secrets(item, plaintexts)?)))
+                .collect::<Result<_>>()?,
+        )),
+        value => Ok(value),
+    }
+}
+
+/// decrypt a secret value of the stack via the pulumi service
+/// the plaintext is the json encoded value of the secret
+pub async fn decrypt_pulumi_secret(
+    path: &str,
+    pulumi_token: Option<String>,
+    ciphertext: &str,
+) -> Result<Value> {
+    let client = get_pulumi_decrypt_client(path, pulumi_token)?;
+    let response: Value = client
+        .json(&json!({ "ciphertext": ciphertext }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
 
-    let result = response
-        .dot_get::<serde_json::Value>("deployment.resources.0.outputs")
-        .unwrap()
-        .unwrap_or_default();
+    let plaintext = response
+        .get("plaintext")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("no plaintext in the pulumi decrypt response"))?;
 
-    Ok(result)
+    Ok(serde_json::from_slice(&BASE64.decode(plaintext)?)?)
 }
 
 pub fn get_pulumi_endpoint() -> Result<String> {
@@ -112,45 +270,62 @@ pub fn get_pulumi_client(
     path: String,
     pulumi_token: Option<String>,
 ) -> Result<reqwest::RequestBuilder> {
-    let token = get_pulumi_token(pulumi_token)?;
     let pulumi_api_endpoint = get_pulumi_endpoint()?;
+    let request = reqwest::Client::new().get(format!("{pulumi_api_endpoint}/{path}/export"));
+
+    with_pulumi_headers(request, pulumi_token)
+}
+
+pub fn get_pulumi_decrypt_client(
+    path: &str,
+    pulumi_token: Option<String>,
+) -> Result<reqwest::RequestBuilder> {
+    let pulumi_api_endpoint = get_pulumi_endpoint()?;
+    let request = reqwest::Client::new().post(format!("{pulumi_api_endpoint}/{path}/decrypt"));
+
+    with_pulumi_headers(request, pulumi_token)
+}
 
+fn with_pulumi_headers(
+    request: reqwest::RequestBuilder,
+    pulumi_token: Option<String>,
+) -> Result<reqwest::RequestBuilder> {
+    let token = get_pulumi_token(pulumi_token)?;
     let authorization = format!("token {token}");
 
-    let client = reqwest::Client::new()
-        .get(format!("{pulumi_api_endpoint}/{path}/export"))
+    Ok(request
         .header("Accept", "application/vnd.pulumi+8")
         .header("Content-Type", "application/json")
-        .header("Authorization", authorization);
-
-    Ok(client)
+        .header("Authorization", authorization))
 }
 
-/// get the output value from the cloudformation stack
+/// get the output value from the pulumi stack
 pub async fn get_pulumi_output(
     path: String,
     remote_path: String,
+    path_mode: &PathMode,
     pulumi_token: Option<String>,
+    pulumi_passphrase: Option<String>,
 ) -> Result<String> {
-    let outputs = get_pulumi_outputs(path, pulumi_token).await?;
-    let result = outputs
-        .dot_get::<serde_json::Value>(remote_path.as_ref())
-        .unwrap()
-        .unwrap();
-
-    Ok(result.to_string())
+    let outputs = get_pulumi_outputs(path, pulumi_token, pulumi_passphrase).await?;
+    get_document_value(&outputs, &remote_path, path_mode)
+        .map_err(|err| anyhow!("no output found at {remote_path}: {err}"))
 }
 
 // get the secret data from the whole outputs of the pulumi stack
 pub async fn get_pulumi_outputs_as_secret_data(
     path: String,
     pulumi_token: Option<String>,
+    pulumi_passphrase: Option<String>,
 ) -> Result<BTreeMap<String, ByteString>> {
-    let outputs = get_pulumi_outputs(path, pulumi_token).await?;
+    let outputs = get_pulumi_outputs(path, pulumi_token, pulumi_passphrase).await?;
     let mut secrets = BTreeMap::new();
-    for (key, value) in outputs.as_object().unwrap() {
+    let outputs = outputs
+        .as_object()
+        .ok_or_else(|| anyhow!("pulumi stack outputs are not an object"))?;
+    for (key, value) in outputs {
         let remote_path = key.to_string();
-        let value_string = value.to_string();
+        let value_string = json_value_to_string(value);
         let output_value = ByteString(value_string.as_bytes().to_vec());
         secrets.insert(remote_path, output_value);
     }
@@ -165,9 +340,55 @@ mod tests {
     #[tokio::test]
     async fn test_get_vault_value() {
         if std::env::var("PULUMI_ACCESS_TOKEN").is_ok() {
-            let result2 = get_pulumi_outputs("sharonlucky11/test/dev".to_string(), None).await;
+            let result2 =
+                get_pulumi_outputs("sharonlucky11/test/dev".to_string(), None, None).await;
 
             println!("{result2:?}");
         }
     }
+
+    #[test]
+    fn test_get_stack_resource_outputs() {
+        let deployment = json!({
+            "resources": [
+                { "type": "pulumi:providers:aws", "outputs": { "region": "ap-southeast-2" } },
+                { "type": "pulumi:pulumi:Stack", "outputs": { "bucket": "my-bucket" } }
+            ]
+        });
+
+        let outputs = get_stack_resource_outputs(&deployment).unwrap();
+
+        assert_eq!(outputs, json!({ "bucket": "my-bucket" }));
+        assert!(get_stack_resource_outputs(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_reveal_secrets() {
+        let secret = |ciphertext: &str| json!({ PULUMI_SECRET_SIG_KEY: PULUMI_SECRET_SIG, "ciphertext": ciphertext });
+        let outputs = json!({
+            "password": secret("c1"),
+            "nested": { "token": secret("c2"), "port": 5432 },
+            "shown": { PULUMI_SECRET_SIG_KEY: PULUMI_SECRET_SIG, "plaintext": "\"visible\"" }
+        });
+
+        let mut ciphertexts = collect_secret_ciphertexts(&outputs);
+        ciphertexts.sort();
+        assert_eq!(ciphertexts, vec!["c1", "c2"]);
+
+        let plaintexts = BTreeMap::from([
+            ("c1".to_string(), json!("hunter2")),
+            ("c2".to_string(), json!({ "id": 1 })),
+        ]);
+        let revealed = reveal_secrets(outputs, &plaintexts).unwrap();
+
+        assert_eq!(
+            revealed,
+            json!({
+                "password": "hunter2",
+                "nested": { "token": { "id": 1 }, "port": 5432 },
+                "shown": "visible"
+            })
+        );
+        assert!(reveal_secrets(secret("c3"), &plaintexts).is_err());
+    }
 }
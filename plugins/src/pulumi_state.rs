@@ -0,0 +1,283 @@
+use crate::aws_common::read_s3_url;
+use crate::pulumi::{collect_secret_ciphertexts, get_stack_resource_outputs, reveal_secrets};
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use cached::macros::cached;
+use pbkdf2::pbkdf2_hmac;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// pbkdf2 parameters used by the pulumi passphrase secrets provider
+const PASSPHRASE_KDF_ITERATIONS: u32 = 1_000_000;
+const PASSPHRASE_KEY_LENGTH: usize = 32;
+const AES_GCM_NONCE_LENGTH: usize = 12;
+/// plaintext of the encrypted salt state, used to verify the passphrase
+const PASSPHRASE_CHECK: &[u8] = b"pulumi";
+
+/// whether the stack path points at a checkpoint of a self-managed backend
+pub fn is_self_managed_state(path: &str) -> bool {
+    path.starts_with("s3://") || path.starts_with("file://")
+}
+
+/// read the checkpoint json eg s3://bucket/.pulumi/stacks/project/dev.json
+pub async fn read_pulumi_checkpoint(path: &str) -> Result<Value> {
+    let bytes = match path.strip_prefix("file://") {
+        Some(file_path) => {
+            let state_dir = std::env::var("PULUMI_STATE_DIR").ok();
+            std::fs::read(get_state_file_path(file_path, state_dir.as_deref())?)?
+        }
+        None => read_s3_url(path).await?,
+    };
+
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// file checkpoints are only read below the directory set by the operator in PULUMI_STATE_DIR,
+/// so the rsecret authors can not read the other files of the controller
+pub fn get_state_file_path(file_path: &str, state_dir: Option<&str>) -> Result<PathBuf> {
+    let state_dir = state_dir.ok_or_else(|| {
+        anyhow!("file checkpoints are disabled, set PULUMI_STATE_DIR to read them")
+    })?;
+
+    // symlinks and .. are resolved before the path is checked
+    let state_dir = std::fs::canonicalize(state_dir)?;
+    let path = std::fs::canonicalize(file_path)
+        .map_err(|err| anyhow!("failed to read the checkpoint {file_path}: {err}"))?;
+    if !path.starts_with(&state_dir) {
+        return Err(anyhow!(
+            "the checkpoint {file_path} is outside of PULUMI_STATE_DIR"
+        ));
+    }
+
+    Ok(path)
+}
+
+/// get the outputs of the stack resource from a self-managed checkpoint
+/// secret outputs are decrypted with the passphrase
+pub async fn get_pulumi_state_outputs(path: &str, passphrase: Option<String>) -> Result<Value> {
+    let checkpoint = read_pulumi_checkpoint(path).await?;
+    let deployment = checkpoint
+        .pointer("/checkpoint/latest")
+        .ok_or_else(|| anyhow!("no latest deployment found in the checkpoint {path}"))?;
+
+    let outputs = get_stack_resource_outputs(deployment)?;
+    let ciphertexts = collect_secret_ciphertexts(&outputs);
+    if ciphertexts.is_empty() {
+        return Ok(outputs);
+    }
+
+    let passphrase = passphrase.ok_or_else(|| {
+        anyhow!("pulumi_passphrase_secret_ref or PULUMI_CONFIG_PASSPHRASE is required to decrypt the secret outputs")
+    })?;
+    let crypter = PassphraseCrypter::from_deployment(deployment, passphrase).await?;
+
+    let mut plaintexts = BTreeMap::new();
+    for ciphertext in ciphertexts {
+        let plaintext = crypter.decrypt(&ciphertext)?;
+        plaintexts.insert(ciphertext, plaintext);
+    }
+
+    reveal_secrets(outputs, &plaintexts)
+}
+
+/// decrypts the secrets of a stack using the passphrase secrets provider
+pub struct PassphraseCrypter {
+    key: Vec<u8>,
+}
+
+impl PassphraseCrypter {
+    /// derive the key from the salt state of the deployment and verify the passphrase
+    pub async fn from_deployment(
+        deployment: &Value,
+        passphrase: String,
+    ) -> Result<PassphraseCrypter> {
+        let provider = deployment
+            .pointer("/secrets_providers/type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if provider != "passphrase" {
+            return Err(anyhow!(
+                "unsupported pulumi secrets provider {provider}, only passphrase is supported"
+            ));
+        }
+
+        let state = deployment
+            .pointer("/secrets_providers/state/salt")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("no salt found for the passphrase secrets provider"))?;
+
+        PassphraseCrypter::from_salt_state(state, passphrase).await
+    }
+
+    /// salt state is in the format of v1:salt:v1:nonce:ciphertext
+    pub async fn from_salt_state(state: &str, passphrase: String) -> Result<PassphraseCrypter> {
+        let mut parts = state.splitn(3, ':');
+        let (Some("v1"), Some(salt), Some(check)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("invalid passphrase salt state"));
+        };
+
+        let key = derive_passphrase_key(BASE64.decode(salt)?, passphrase).await?;
+
+        let crypter = PassphraseCrypter { key };
+        match crypter.decrypt_raw(check) {
+            Ok(plaintext) if plaintext == PASSPHRASE_CHECK => Ok(crypter),
+            _ => Err(anyhow!("incorrect passphrase for the pulumi stack")),
+        }
+    }
+
+    /// decrypt a secret value, the plaintext is the json encoded value of the secret
+    pub fn decrypt(&self, ciphertext: &str) -> Result<Value> {
+        Ok(serde_json::from_slice(&self.decrypt_raw(ciphertext)?)?)
+    }
+
+    /// ciphertext is in the format of v1:nonce:ciphertext with the gcm tag appended
+    fn decrypt_raw(&self, ciphertext: &str) -> Result<Vec<u8>> {
+        let mut parts = ciphertext.split(':');
+        let (Some("v1"), Some(nonce), Some(data), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(anyhow!("invalid pulumi secret ciphertext"));
+        };
+
+        let nonce = BASE64.decode(nonce)?;
+        if nonce.len() != AES_GCM_NONCE_LENGTH {
+            return Err(anyhow!("invalid pulumi secret nonce"));
+        }
+
+        Aes256Gcm::new_from_slice(&self.key)?
+            .decrypt(Nonce::from_slice(&nonce), BASE64.decode(data)?.as_slice())
+            .map_err(|_| anyhow!("failed to decrypt the pulumi secret"))
+    }
+}
+
+/// the 1,000,000 rounds of pbkdf2 run on the blocking threads, not on the reconcile
+/// Will cache the key by salt and passphrase, keyed on their hash
+#[cached(
+    max_size = 64,
+    key = "Vec<u8>",
+    convert = r#"{ Sha256::new().chain_update(&salt).chain_update(&passphrase).finalize().to_vec() }"#
+)]
+async fn derive_passphrase_key(salt: Vec<u8>, passphrase: String) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let mut key = vec![0; PASSPHRASE_KEY_LENGTH];
+        pbkdf2_hmac::<Sha256>(
+            passphrase.as_bytes(),
+            &salt,
+            PASSPHRASE_KDF_ITERATIONS,
+            &mut key,
+        );
+        key
+    })
+    .await
+    .map_err(|err| anyhow!("failed to derive the pulumi passphrase key: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SALT: &[u8] = b"0123456789abcdef";
+    const NONCE: &[u8] = b"nonce-012345";
+
+    fn derive_key(passphrase: &str) -> Vec<u8> {
+        let mut key = vec![0; PASSPHRASE_KEY_LENGTH];
+        pbkdf2_hmac::<Sha256>(
+            passphrase.as_bytes(),
+            SALT,
+            PASSPHRASE_KDF_ITERATIONS,
+            &mut key,
+        );
+        key
+    }
+
+    fn encrypt(key: &[u8], plaintext: &[u8]) -> String {
+        // the gcm tag is appended to the ciphertext
+        let data = Aes256Gcm::new_from_slice(key)
+            .unwrap()
+            .encrypt(Nonce::from_slice(NONCE), plaintext)
+            .unwrap();
+        format!("v1:{}:{}", BASE64.encode(NONCE), BASE64.encode(data))
+    }
+
+    #[tokio::test]
+    async fn test_get_pulumi_state_outputs() {
+        let key = derive_key("correct horse");
+        let salt_state = format!(
+            "v1:{}:{}",
+            BASE64.encode(SALT),
+            encrypt(&key, PASSPHRASE_CHECK)
+        );
+        let checkpoint = json!({
+            "version": 3,
+            "checkpoint": {
+                "latest": {
+                    "secrets_providers": { "type": "passphrase", "state": { "salt": salt_state } },
+                    "resources": [{
+                        "type": "pulumi:pulumi:Stack",
+                        "outputs": {
+                            "bucket": "my-bucket",
+                            "password": {
+                                "4dabf18193072939515e22adb298388d": "1b47061264138c4ac30d75fd1eb44270",
+                                "ciphertext": encrypt(&key, br#""hunter2""#)
+                            }
+                        }
+                    }]
+                }
+            }
+        });
+
+        let path = std::env::temp_dir().join("remote-secrets-pulumi-state-test.json");
+        std::fs::write(&path, checkpoint.to_string()).unwrap();
+        let url = format!("file://{}", path.display());
+        std::env::set_var("PULUMI_STATE_DIR", std::env::temp_dir());
+
+        let outputs = get_pulumi_state_outputs(&url, Some("correct horse".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            outputs,
+            json!({ "bucket": "my-bucket", "password": "hunter2" })
+        );
+
+        let wrong = get_pulumi_state_outputs(&url, Some("wrong".to_string())).await;
+        assert!(wrong.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_get_state_file_path() {
+        let state_dir = std::env::temp_dir().join("remote-secrets-pulumi-state-dir");
+        std::fs::create_dir_all(&state_dir).unwrap();
+        let checkpoint = state_dir.join("dev.json");
+        std::fs::write(&checkpoint, "{}").unwrap();
+        let state_dir = state_dir.to_str().unwrap();
+
+        let path = get_state_file_path(checkpoint.to_str().unwrap(), Some(state_dir)).unwrap();
+        assert!(path.ends_with("dev.json"));
+
+        let escaping = format!("{state_dir}/../remote-secrets-pulumi-state-dir/../../etc/hostname");
+        assert!(get_state_file_path(&escaping, Some(state_dir)).is_err());
+        assert!(get_state_file_path("/etc/hostname", Some(state_dir)).is_err());
+        assert!(get_state_file_path(checkpoint.to_str().unwrap(), None).is_err());
+
+        std::fs::remove_dir_all(state_dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_self_managed_state() {
+        assert!(is_self_managed_state(
+            "s3://bucket/.pulumi/stacks/app/dev.json"
+        ));
+        assert!(is_self_managed_state(
+            "file:///state/.pulumi/stacks/app/dev.json"
+        ));
+        assert!(!is_self_managed_state("org/app/dev"));
+    }
+}
@@ -0,0 +1,259 @@
+use crd::{Backend, BackendType, RemoteValue};
+
+use anyhow::{anyhow, Result};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use k8s_openapi::api::core::v1::Secret;
+use kube::Client;
+use lazy_static::lazy_static;
+use schemars::{schema_for, Schema};
+use serde_json::Value;
+use std::sync::Mutex;
+
+/// fields of every backend, whatever its provider
+const COMMON_FIELDS: [&str; 3] = ["backend", "data", "rewrite"];
+
+/// what the dispatcher knows about the rsecret when resolving a backend
+#[derive(Clone, Copy)]
+pub struct BackendContext<'a> {
+    /// namespace of the rsecret
+    pub namespace: &'a str,
+    /// name of the rsecret
+    pub name: &'a str,
+    /// secret of the rsecret, if any
+    pub existing: Option<&'a Secret>,
+    /// kubernetes client of the controller, the providers read the kubernetes objects with it
+    pub client: &'a Client,
+}
+
+pub type ProviderFactory = fn(&Backend, &BackendContext<'_>) -> Box<dyn RemoteValue + Send + Sync>;
+
+/// a backend provider, registered under the name of its backend type
+#[derive(Clone, Copy)]
+pub struct Provider {
+    /// name of the backend type in the rsecret spec
+    pub name: &'static str,
+    /// fields of the backend the provider reads besides backend, data and rewrite
+    pub fields: &'static [&'static str],
+    /// fields the provider can not do without
+    pub required_fields: &'static [&'static str],
+    /// creates the remote value of a backend of the rsecret
+    pub factory: ProviderFactory,
+}
+
+impl Provider {
+    /// provider configured by the data of the backend only
+    pub fn new(name: &'static str, factory: ProviderFactory) -> Provider {
+        Provider {
+            name,
+            fields: &[],
+            required_fields: &[],
+            factory,
+        }
+    }
+
+    pub fn with_fields(mut self, fields: &'static [&'static str]) -> Provider {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_required_fields(mut self, fields: &'static [&'static str]) -> Provider {
+        self.required_fields = fields;
+        self
+    }
+
+    /// json schema of the backend spec of the provider, the schema of the backend
+    /// restricted to the fields the provider reads
+    pub fn spec_schema(&self) -> Schema {
+        let mut schema = schema_for!(Backend);
+        let is_read = |field: &str| {
+            COMMON_FIELDS.contains(&field)
+                || self.fields.contains(&field)
+                || self.required_fields.contains(&field)
+        };
+
+        if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+            properties.retain(|field, _| is_read(field));
+        }
+        if let Value::Array(required) = schema
+            .ensure_object()
+            .entry("required")
+            .or_insert(Value::Array(vec![]))
+        {
+            required.extend(self.required_fields.iter().map(|field| Value::from(*field)));
+        }
+
+        schema
+    }
+
+    /// check the backend against the spec schema, a field the provider does not read is refused
+    /// instead of ignored, like token_secret_ref on a backend without tokens
+    pub fn validate(&self, backend: &Backend) -> Result<()> {
+        let schema = self.spec_schema();
+        let Value::Object(fields) = serde_json::to_value(backend)? else {
+            return Err(anyhow!("the {} backend is not an object", self.name));
+        };
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (field, value) in fields.iter() {
+            if !value.is_null()
+                && !properties.is_some_and(|properties| properties.contains_key(field))
+            {
+                return Err(anyhow!(
+                    "{field} is not a field of the {} backend",
+                    self.name
+                ));
+            }
+        }
+
+        let required = schema.get("required").and_then(Value::as_array);
+        for field in required.into_iter().flatten().filter_map(Value::as_str) {
+            if fields.get(field).is_none_or(Value::is_null) {
+                return Err(anyhow!("{field} is required by the {} backend", self.name));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref PROVIDERS: Vec<Provider> = {
+        let mut providers = Vec::new();
+        crate::register_providers(&mut providers);
+        providers
+    };
+    /// rsecrets the providers ask to resolve again, eg when a watched value changes
+    static ref RECONCILE_REQUESTS: Mutex<Option<UnboundedSender<(String, String)>>> =
+        Mutex::new(None);
+}
+
+/// the namespace and name of the rsecrets to reconcile again, requested by the providers
+pub fn reconcile_requests() -> UnboundedReceiver<(String, String)> {
+    let (sender, receiver) = unbounded();
+    *RECONCILE_REQUESTS.lock().unwrap() = Some(sender);
+    receiver
+}
+
+/// ask the controller to reconcile the rsecret again, ignored when nothing listens
+pub fn request_reconcile(namespace: &str, name: &str) {
+    if let Some(sender) = RECONCILE_REQUESTS.lock().unwrap().as_ref() {
+        let _ = sender.unbounded_send((namespace.to_owned(), name.to_owned()));
+    }
+}
+
+/// the provider registered under the name
+pub fn get_provider(name: &str) -> Option<&'static Provider> {
+    PROVIDERS.iter().find(|provider| provider.name == name)
+}
+
+/// names of every provider compiled in
+pub fn get_provider_names() -> Vec<&'static str> {
+    PROVIDERS.iter().map(|provider| provider.name).collect()
+}
+
+/// name of the backend type, as written in the rsecret spec
+pub fn backend_name(backend_type: &BackendType) -> String {
+    serde_json::to_value(backend_type)
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::ByteString;
+
+    /// client of an unreachable api server, for the providers that do not read kubernetes objects
+    fn test_client() -> Client {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let config = kube::Config::new("http://127.0.0.1:1".parse().unwrap());
+        Client::try_from(config).unwrap()
+    }
+
+    #[test]
+    fn test_provider_names_are_backend_types() {
+        let names = get_provider_names();
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+
+        for name in names {
+            let backend_type: BackendType = serde_json::from_value(name.into()).unwrap();
+            assert_eq!(backend_name(&backend_type), name);
+        }
+        assert!(get_provider("AppConfig").is_none());
+    }
+
+    #[test]
+    fn test_provider_spec_schemas() {
+        let backend_schema = schema_for!(Backend);
+        let backend_fields = backend_schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .unwrap();
+        for provider in PROVIDERS.iter() {
+            for field in provider.fields.iter().chain(provider.required_fields) {
+                assert!(backend_fields.contains_key(*field), "{field}");
+            }
+        }
+
+        let schema = get_provider("Plaintext").unwrap().spec_schema();
+        let properties = schema.get("properties").and_then(Value::as_object).unwrap();
+        assert!(properties.contains_key("data"));
+        assert!(!properties.contains_key("token_secret_ref"));
+    }
+
+    #[test]
+    fn test_validate_backend() {
+        let backend = |value: Value| -> Backend { serde_json::from_value(value).unwrap() };
+        let plaintext = get_provider("Plaintext").unwrap();
+
+        assert!(plaintext
+            .validate(&backend(serde_json::json!({
+                "backend": "Plaintext",
+                "data": [{ "value": "plain-value", "key": "plain-key" }],
+                "rewrite": [{ "prefix": "app_" }]
+            })))
+            .is_ok());
+        assert!(plaintext
+            .validate(&backend(serde_json::json!({
+                "backend": "Plaintext",
+                "token_secret_ref": { "name": "token", "key": "token" }
+            })))
+            .is_err());
+
+        let external = Provider::new("External", |backend, _| {
+            Box::new(crate::plaintext::PlainText::from_backend(backend))
+        })
+        .with_required_fields(&["external"]);
+        assert!(external
+            .validate(&backend(serde_json::json!({ "backend": "External" })))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_by_name() {
+        let backend: Backend = serde_json::from_value(serde_json::json!({
+            "backend": "Plaintext",
+            "data": [{ "value": "plain-value", "key": "plain-key" }]
+        }))
+        .unwrap();
+        let client = test_client();
+        let context = BackendContext {
+            namespace: "default",
+            name: "example",
+            existing: None,
+            client: &client,
+        };
+
+        let provider = get_provider(&backend_name(&backend.backend)).unwrap();
+        let data = (provider.factory)(&backend, &context)
+            .get_value()
+            .await
+            .data;
+
+        assert_eq!(data["plain-key"], ByteString(b"plain-value".to_vec()));
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use crd::SecretKeyReference;
+use k8s_openapi::api::core::v1::Secret;
+use kube::{Api, Client};
+
+/// read the value of a key of a secret in the namespace of the rsecret
+pub async fn get_secret_ref_value(
+    client: &Client,
+    namespace: &str,
+    secret_ref: &SecretKeyReference,
+) -> Result<String> {
+    let api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let secret = api.get(&secret_ref.name).await?;
+
+    let value = secret
+        .data
+        .and_then(|mut data| data.remove(&secret_ref.key))
+        .ok_or_else(|| {
+            anyhow!(
+                "no key {} found in the secret {namespace}/{}",
+                secret_ref.key,
+                secret_ref.name
+            )
+        })?;
+
+    Ok(String::from_utf8(value.0)?.trim().to_owned())
+}
+
+/// token of a backend from the secret reference, from the environment variable otherwise
+pub async fn get_backend_token(
+    secret_ref: &Option<SecretKeyReference>,
+    namespace: &Option<String>,
+    client: &Option<Client>,
+    env_var: &str,
+) -> Result<Option<String>> {
+    match (secret_ref, namespace, client) {
+        (Some(secret_ref), Some(namespace), Some(client)) => Ok(Some(
+            get_secret_ref_value(client, namespace, secret_ref).await?,
+        )),
+        (Some(secret_ref), _, _) => Err(anyhow!(
+            "no namespace or client to read the token secret {}",
+            secret_ref.name
+        )),
+        _ => Ok(std::env::var(env_var).ok()),
+    }
+}
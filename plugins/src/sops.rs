@@ -0,0 +1,748 @@
+use crate::concurrent::resolve_entries;
+use crate::kubernetes::{read_source_data, SourceKind};
+use crate::registry::Provider;
+use async_trait::async_trait;
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
+
+use aes_gcm::{
+    aead::{consts::U32, generic_array::GenericArray, Aead, KeyInit, Payload},
+    aes::Aes256,
+    AesGcm,
+};
+use age::{armor::ArmoredReader, x25519};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use k8s_openapi::ByteString;
+use kube::Client;
+use serde_json::{Map, Value};
+use serde_yaml::{Mapping, Value as YamlValue};
+use sha2::{Digest, Sha512};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use utils::value::{get_parsed_document_secret_data, json_value_to_string, parse_dotenv_lines};
+
+const SOPS_METADATA_KEY: &str = "sops";
+const SOPS_DOTENV_PREFIX: &str = "sops_";
+
+#[derive(Clone)]
+pub struct Sops {
+    data: Vec<SecretData>,
+    namespace: Option<String>,
+    /// kubernetes client of the controller
+    client: Option<Client>,
+}
+
+#[async_trait]
+impl RemoteValue for Sops {
+    fn from_backend(backend: &Backend) -> Sops {
+        Sops {
+            data: backend.data.clone(),
+            namespace: None,
+            client: None,
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        let identities = match get_age_identities() {
+            Ok(identities) => identities,
+            Err(err) => {
+                log::error!("{err}");
+                return RemoteData::default();
+            }
+        };
+        let identities = &identities;
+
+        resolve_entries(&self.data, |secret_data| async move {
+            let document = match self.get_document(secret_data).await {
+                Ok(document) => decrypt_sops_document(&document, identities).await,
+                Err(err) => Err(err),
+            };
+
+            match document {
+                Ok(document) => get_sops_secret_data(secret_data, &document),
+                Err(err) => {
+                    log::error!("{err}");
+                    BTreeMap::new()
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// sops encrypts the values with AES-256-GCM and a 32 bytes nonce
+type SopsCipher = AesGcm<Aes256, U32>;
+
+/// registers the sops backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Sops", |backend, context| {
+        Box::new(
+            Sops::from_backend(backend)
+                .in_namespace(context.namespace)
+                .with_client(context.client),
+        )
+    }));
+}
+
+impl Sops {
+    /// namespace the config maps holding the documents are read from
+    pub fn in_namespace(mut self, namespace: &str) -> Sops {
+        self.namespace = Some(namespace.to_owned());
+        self
+    }
+
+    /// client the config maps are read with
+    pub fn with_client(mut self, client: &Client) -> Sops {
+        self.client = Some(client.clone());
+        self
+    }
+
+    /// the document is inline in value or in a config map of the namespace of the rsecret
+    async fn get_document(&self, secret_data: &SecretData) -> Result<String> {
+        let Some(config_map_ref) = &secret_data.config_map_ref else {
+            return Ok(secret_data.value.clone());
+        };
+
+        let (Some(namespace), Some(client)) = (&self.namespace, &self.client) else {
+            return Err(anyhow!(
+                "no namespace or client to read the config map {}",
+                config_map_ref.name
+            ));
+        };
+        let mut data = read_source_data(
+            client,
+            SourceKind::ConfigMap,
+            namespace,
+            &config_map_ref.name,
+        )
+        .await?;
+        let document = data.remove(&config_map_ref.key).ok_or_else(|| {
+            anyhow!(
+                "no key {} found in the config map {namespace}/{}",
+                config_map_ref.key,
+                config_map_ref.name
+            )
+        })?;
+
+        Ok(String::from_utf8(document)?)
+    }
+}
+
+/// age identities from SOPS_AGE_KEY or the SOPS_AGE_KEY_FILE, the same as the sops cli
+pub fn get_age_identities() -> Result<Vec<x25519::Identity>> {
+    let identities = match (
+        std::env::var("SOPS_AGE_KEY"),
+        std::env::var("SOPS_AGE_KEY_FILE"),
+    ) {
+        (Ok(identities), _) => identities,
+        (_, Ok(path)) => std::fs::read_to_string(path)?,
+        _ => return Ok(vec![]),
+    };
+
+    parse_age_identities(&identities)
+}
+
+/// identities of a key file, comments and blank lines are skipped
+pub fn parse_age_identities(identities: &str) -> Result<Vec<x25519::Identity>> {
+    identities
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse()
+                .map_err(|err| anyhow!("invalid age identity, {err}"))
+        })
+        .collect()
+}
+
+/// decrypt an armored or binary age file, a truncated file fails on its missing final chunk
+pub fn decrypt_age(file: &[u8], identities: &[x25519::Identity]) -> Result<Vec<u8>> {
+    let decryptor = age::Decryptor::new(ArmoredReader::new(file))?;
+    let mut reader = decryptor.decrypt(
+        identities
+            .iter()
+            .map(|identity| identity as &dyn age::Identity),
+    )?;
+
+    let mut plaintext = vec![];
+    reader.read_to_end(&mut plaintext)?;
+
+    Ok(plaintext)
+}
+
+/// decrypt a sops yaml, json or dotenv document and check its mac
+pub async fn decrypt_sops_document(
+    document: &str,
+    identities: &[x25519::Identity],
+) -> Result<Value> {
+    let (tree, metadata) = parse_sops_document(document)?;
+    let data_key = get_data_key(&metadata, identities).await?;
+
+    let mut mac = SopsMac::new(&metadata);
+    let decrypted = decrypt_tree(&tree, &data_key, &mut vec![], &mut mac)?;
+    mac.check(&metadata, &data_key)?;
+
+    Ok(decrypted)
+}
+
+/// split the document into its tree, in the order of the document, and its sops metadata
+pub fn parse_sops_document(document: &str) -> Result<(YamlValue, Value)> {
+    // json documents are yaml documents too
+    let mut tree = match serde_yaml::from_str::<Mapping>(document) {
+        Ok(tree) if tree.contains_key(SOPS_METADATA_KEY) => tree,
+        _ => return parse_sops_dotenv(document),
+    };
+
+    let metadata = tree
+        .remove(SOPS_METADATA_KEY)
+        .ok_or_else(|| anyhow!("no sops metadata found in the document"))?;
+
+    Ok((YamlValue::Mapping(tree), serde_json::to_value(metadata)?))
+}
+
+/// dotenv documents keep the metadata in flattened sops_ prefixed variables
+/// eg sops_kms__list_0__map_arn
+fn parse_sops_dotenv(document: &str) -> Result<(YamlValue, Value)> {
+    let mut tree = Mapping::new();
+    let mut metadata = Value::Null;
+
+    for (name, value) in parse_dotenv_lines(document)? {
+        let value = value.replace("\\n", "\n");
+        match name.strip_prefix(SOPS_DOTENV_PREFIX) {
+            Some(flattened) => {
+                let segments: Vec<&str> = flattened.split("__").collect();
+                insert_flattened(&mut metadata, &segments, value);
+            }
+            None => {
+                tree.insert(YamlValue::String(name), YamlValue::String(value));
+            }
+        }
+    }
+
+    if metadata.is_null() {
+        return Err(anyhow!("no sops metadata found in the document"));
+    }
+
+    Ok((YamlValue::Mapping(tree), metadata))
+}
+fn insert_flattened(node: &mut Value, segments: &[&str], value: String) {
+    let Some((segment, rest)) = segments.split_first() else {
+        *node = Value::String(value);
+        return;
+    };
+
+    let child = match segment
+        .strip_prefix("list_")
+        .and_then(|index| index.parse::<usize>().ok())
+    {
+        Some(index) => {
+            if !node.is_array() {
+                *node = Value::Array(vec![]);
+            }
+            let list = node.as_array_mut().unwrap();
+            if list.len() <= index {
+                list.resize(index + 1, Value::Null);
+            }
+            &mut list[index]
+        }
+        None => {
+            if !node.is_object() {
+                *node = Value::Object(Map::new());
+            }
+            let key = segment.strip_prefix("map_").unwrap_or(segment);
+            node.as_object_mut()
+                .unwrap()
+                .entry(key.to_owned())
+                .or_insert(Value::Null)
+        }
+    };
+
+    insert_flattened(child, rest, value);
+}
+
+/// decrypt the data key with the first age identity or kms key that works
+async fn get_data_key(metadata: &Value, identities: &[x25519::Identity]) -> Result<Vec<u8>> {
+    let mut errors = vec![];
+
+    if !identities.is_empty() {
+        for recipient in entries(metadata, "age") {
+            let enc = recipient
+                .get("enc")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match decrypt_age(enc.as_bytes(), identities) {
+                Ok(data_key) => return Ok(data_key),
+                Err(err) => errors.push(format!("age: {err}")),
+            }
+        }
+    }
+
+    for key in entries(metadata, "kms") {
+        let arn = key.get("arn").and_then(Value::as_str).unwrap_or_default();
+        match decrypt_kms_data_key(key).await {
+            Ok(data_key) => return Ok(data_key),
+            Err(err) => errors.push(format!("kms {arn}: {err}")),
+        }
+    }
+
+    Err(anyhow!(
+        "failed to decrypt the sops data key [{}]",
+        errors.join(", ")
+    ))
+}
+
+#[cfg(feature = "aws")]
+async fn decrypt_kms_data_key(key: &Value) -> Result<Vec<u8>> {
+    let arn = key.get("arn").and_then(Value::as_str).unwrap_or_default();
+    let enc = key.get("enc").and_then(Value::as_str).unwrap_or_default();
+    let region = arn.split(':').nth(3).map(str::to_owned);
+    let context = key
+        .get("context")
+        .and_then(Value::as_object)
+        .map(|context| {
+            context
+                .iter()
+                .map(|(name, value)| (name.to_owned(), json_value_to_string(value)))
+                .collect()
+        });
+
+    crate::aws_kms::decrypt_kms_ciphertext(enc.to_owned(), context, region).await
+}
+
+#[cfg(not(feature = "aws"))]
+async fn decrypt_kms_data_key(_key: &Value) -> Result<Vec<u8>> {
+    Err(anyhow!("kms data keys require the aws feature"))
+}
+
+fn entries<'a>(metadata: &'a Value, name: &str) -> impl Iterator<Item = &'a Value> {
+    metadata
+        .get(name)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// sha512 of the cleartext values in the order of the document, as computed by sops
+/// comments are part of the sops mac, documents with comments are not supported
+struct SopsMac {
+    hash: Sha512,
+    only_encrypted: bool,
+}
+
+impl SopsMac {
+    fn new(metadata: &Value) -> SopsMac {
+        let only_encrypted = match metadata.get("mac_only_encrypted") {
+            Some(Value::Bool(only_encrypted)) => *only_encrypted,
+            Some(Value::String(only_encrypted)) => only_encrypted == "true",
+            _ => false,
+        };
+
+        SopsMac {
+            hash: Sha512::new(),
+            only_encrypted,
+        }
+    }
+
+    /// values are hashed the way sops converts them to bytes, eg True for true
+    fn add(&mut self, value: &Value, encrypted: bool) {
+        if self.only_encrypted && !encrypted {
+            return;
+        }
+        let bytes = match value {
+            Value::String(value) => value.to_owned(),
+            Value::Bool(true) => "True".to_owned(),
+            Value::Bool(false) => "False".to_owned(),
+            Value::Number(number) => match number.as_f64() {
+                Some(float) if !number.is_i64() && !number.is_u64() => float.to_string(),
+                _ => number.to_string(),
+            },
+            _ => return,
+        };
+        self.hash.update(bytes.as_bytes());
+    }
+
+    /// the mac of the metadata is encrypted with the lastmodified date as additional data
+    fn check(self, metadata: &Value, data_key: &[u8]) -> Result<()> {
+        let mac = metadata
+            .get("mac")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("no mac found in the sops metadata"))?;
+        let last_modified = metadata
+            .get("lastmodified")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("no lastmodified found in the sops metadata"))?;
+
+        let expected = decrypt_sops_value(mac, data_key, last_modified)?;
+        let computed: String = self
+            .hash
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+
+        if expected.as_str() != Some(computed.as_str()) {
+            return Err(anyhow!(
+                "the mac of the sops document does not match, it was modified or it has comments"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// the additional data of every value is its path in the tree, eg db:password:
+fn decrypt_tree(
+    value: &YamlValue,
+    data_key: &[u8],
+    path: &mut Vec<String>,
+    mac: &mut SopsMac,
+) -> Result<Value> {
+    match value {
+        YamlValue::Mapping(mapping) => {
+            let mut decrypted = Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    YamlValue::String(key) => key.to_owned(),
+                    key => json_value_to_string(&serde_json::to_value(key)?),
+                };
+                path.push(key.clone());
+                let value = decrypt_tree(value, data_key, path, mac);
+                path.pop();
+                decrypted.insert(key, value?);
+            }
+            Ok(Value::Object(decrypted))
+        }
+        YamlValue::Sequence(list) => list
+            .iter()
+            .map(|value| decrypt_tree(value, data_key, path, mac))
+            .collect::<Result<Vec<Value>>>()
+            .map(Value::Array),
+        YamlValue::Tagged(tagged) => decrypt_tree(&tagged.value, data_key, path, mac),
+        YamlValue::String(value) if value.starts_with("ENC[") => {
+            let decrypted = decrypt_sops_value(value, data_key, &format!("{}:", path.join(":")))?;
+            mac.add(&decrypted, true);
+            Ok(decrypted)
+        }
+        value => {
+            let value = serde_json::to_value(value)?;
+            mac.add(&value, false);
+            Ok(value)
+        }
+    }
+}
+
+/// decrypt ENC[AES256_GCM,data:...,iv:...,tag:...,type:str]
+pub fn decrypt_sops_value(value: &str, data_key: &[u8], additional_data: &str) -> Result<Value> {
+    let fields: BTreeMap<&str, &str> = value
+        .strip_prefix("ENC[AES256_GCM,")
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or_else(|| anyhow!("unsupported sops value at {additional_data}"))?
+        .split(',')
+        .filter_map(|field| field.split_once(':'))
+        .collect();
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("no {name} in the sops value at {additional_data}"))
+    };
+
+    let cipher = SopsCipher::new_from_slice(data_key)
+        .map_err(|_| anyhow!("invalid sops data key, expected 32 bytes"))?;
+    let iv = BASE64.decode(field("iv")?)?;
+    if iv.len() != 32 {
+        return Err(anyhow!("invalid iv of the sops value at {additional_data}"));
+    }
+    let mut message = BASE64.decode(field("data")?)?;
+    message.extend(BASE64.decode(field("tag")?)?);
+
+    let plaintext = cipher
+        .decrypt(
+            GenericArray::from_slice(&iv),
+            Payload {
+                msg: &message,
+                aad: additional_data.as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("failed to decrypt the sops value at {additional_data}"))?;
+    let plaintext = String::from_utf8(plaintext)?;
+
+    Ok(match field("type")? {
+        "int" => Value::from(plaintext.parse::<i64>()?),
+        "float" => Value::from(plaintext.parse::<f64>()?),
+        "bool" => Value::Bool(plaintext.eq_ignore_ascii_case("true")),
+        _ => Value::String(plaintext),
+    })
+}
+
+/// the decrypted document mapped like the documents of the other backends, flattened without key
+pub fn get_sops_secret_data(
+    secret_data: &SecretData,
+    document: &Value,
+) -> BTreeMap<String, ByteString> {
+    get_parsed_document_secret_data(secret_data, document).unwrap_or_else(|err| {
+        log::error!("{err} in the sops document");
+        BTreeMap::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::{armor::ArmoredWriter, armor::Format, secrecy::ExposeSecret};
+    use rand_core::{OsRng, RngCore};
+    use std::io::Write;
+
+    /// age vectors of the C2SP testkit, made with the reference age implementation,
+    /// a header, an empty line and the age file
+    fn age_vector(name: &str) -> (BTreeMap<String, String>, Vec<u8>) {
+        let path = format!("{}/testdata/age/{name}", env!("CARGO_MANIFEST_DIR"));
+        let vector = std::fs::read(path).unwrap();
+        let split = vector
+            .windows(2)
+            .position(|window| window == b"\n\n")
+            .unwrap();
+
+        let header = std::str::from_utf8(&vector[..split])
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+
+        (header, vector[split + 2..].to_vec())
+    }
+
+    #[test]
+    fn test_decrypt_age_vectors() {
+        for name in ["x25519", "armor"] {
+            let (header, file) = age_vector(name);
+            let identities = parse_age_identities(&header["identity"]).unwrap();
+
+            assert_eq!(header["expect"], "success");
+            assert_eq!(decrypt_age(&file, &identities).unwrap(), b"age");
+        }
+
+        for name in [
+            "stream_no_final",
+            "stream_no_final_two_chunks",
+            "x25519_bad_tag",
+        ] {
+            let (header, file) = age_vector(name);
+            let identities = parse_age_identities(&header["identity"]).unwrap();
+
+            assert_ne!(header["expect"], "success");
+            assert!(decrypt_age(&file, &identities).is_err(), "{name}");
+        }
+
+        let (_, file) = age_vector("x25519");
+        let other = vec![x25519::Identity::generate()];
+        assert!(decrypt_age(&file, &other).is_err());
+    }
+
+    #[test]
+    fn test_parse_age_identities() {
+        let (header, _) = age_vector("x25519");
+        let identities = format!("# created: today\n\n{}\n", header["identity"]);
+
+        assert_eq!(parse_age_identities(&identities).unwrap().len(), 1);
+        assert!(parse_age_identities("AGE-SECRET-KEY-1").is_err());
+    }
+
+    fn encrypt_value(
+        plaintext: &str,
+        data_key: &[u8],
+        additional_data: &str,
+        kind: &str,
+    ) -> String {
+        let mut iv = [0; 32];
+        OsRng.fill_bytes(&mut iv);
+        let sealed = SopsCipher::new_from_slice(data_key)
+            .unwrap()
+            .encrypt(
+                GenericArray::from_slice(&iv),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: additional_data.as_bytes(),
+                },
+            )
+            .unwrap();
+        let (data, tag) = sealed.split_at(sealed.len() - 16);
+
+        format!(
+            "ENC[AES256_GCM,data:{},iv:{},tag:{},type:{kind}]",
+            BASE64.encode(data),
+            BASE64.encode(iv),
+            BASE64.encode(tag)
+        )
+    }
+
+    const LAST_MODIFIED: &str = "2026-01-01T00:00:00Z";
+
+    /// the encrypted mac of the cleartext values, in the order of the document
+    fn encrypt_mac(values: &[&str], data_key: &[u8]) -> String {
+        let hash = Sha512::digest(values.concat().as_bytes());
+        let mac: String = hash.iter().map(|byte| format!("{byte:02X}")).collect();
+
+        encrypt_value(&mac, data_key, LAST_MODIFIED, "str")
+    }
+
+    /// data key, its age encrypted form and the identities able to decrypt it
+    fn data_key() -> (Vec<u8>, String, Vec<x25519::Identity>) {
+        let mut data_key = vec![0; 32];
+        OsRng.fill_bytes(&mut data_key);
+        let identity = x25519::Identity::generate();
+        let recipient = identity.to_public();
+
+        let encryptor =
+            age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))
+                .unwrap();
+        let mut enc = vec![];
+        let armored = ArmoredWriter::wrap_output(&mut enc, Format::AsciiArmor).unwrap();
+        let mut writer = encryptor.wrap_output(armored).unwrap();
+        writer.write_all(&data_key).unwrap();
+        writer.finish().unwrap().finish().unwrap();
+
+        let identity = identity.to_string().expose_secret().to_owned();
+        (
+            data_key,
+            String::from_utf8(enc).unwrap(),
+            parse_age_identities(&identity).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_sops_yaml() {
+        let (data_key, enc, identities) = data_key();
+        let document = format!(
+            "password: {}\nport: {}\ndb:\n  user: {}\nregion: us-east-1\nsops:\n  age:\n    - recipient: age1test\n      enc: |\n{}  lastmodified: \"{LAST_MODIFIED}\"\n  mac: {}\n  version: 3.9.0\n",
+            encrypt_value("hunter2", &data_key, "password:", "str"),
+            encrypt_value("5432", &data_key, "port:", "int"),
+            encrypt_value("app", &data_key, "db:user:", "str"),
+            enc.lines()
+                .map(|line| format!("        {line}\n"))
+                .collect::<String>(),
+            encrypt_mac(&["hunter2", "5432", "app", "us-east-1"], &data_key)
+        );
+
+        let decrypted = decrypt_sops_document(&document, &identities).await.unwrap();
+        let data = get_sops_secret_data(&SecretData::default(), &decrypted);
+
+        assert_eq!(data["password"].0, b"hunter2");
+        assert_eq!(data["port"].0, b"5432");
+        assert_eq!(data["region"].0, b"us-east-1");
+        assert_eq!(data["db.user"].0, b"app");
+
+        let nested_as_json = SecretData {
+            flatten: Some(crd::JsonFlatten {
+                separator: None,
+                nested_as_json: Some(true),
+            }),
+            ..SecretData::default()
+        };
+        assert_eq!(
+            get_sops_secret_data(&nested_as_json, &decrypted)["db"].0,
+            br#"{"user":"app"}"#
+        );
+
+        let r_data = SecretData {
+            key: Some("db-user".to_string()),
+            remote_path: Some("db.user".to_string()),
+            ..SecretData::default()
+        };
+        assert_eq!(
+            get_sops_secret_data(&r_data, &decrypted)["db-user"].0,
+            b"app"
+        );
+
+        let r_data = SecretData {
+            key: Some("db-user".to_string()),
+            remote_path: Some("$.db.user".to_string()),
+            path_mode: Some(crd::PathMode::JsonPath),
+            ..SecretData::default()
+        };
+        assert_eq!(
+            get_sops_secret_data(&r_data, &decrypted)["db-user"].0,
+            b"app"
+        );
+
+        // the unencrypted values are authenticated by the mac
+        let modified = document.replace("us-east-1", "eu-west-1");
+        assert!(decrypt_sops_document(&modified, &identities).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_sops_json() {
+        let (data_key, enc, identities) = data_key();
+        let document = format!(
+            r#"{{"token": "{}", "enabled": "{}", "sops": {{"age": [{{"recipient": "age1test", "enc": {}}}], "lastmodified": "{LAST_MODIFIED}", "mac": "{}", "mac_only_encrypted": true}}}}"#,
+            encrypt_value("abc", &data_key, "token:", "str"),
+            encrypt_value("True", &data_key, "enabled:", "bool"),
+            serde_json::to_string(&enc).unwrap(),
+            encrypt_mac(&["abc", "True"], &data_key)
+        );
+
+        let decrypted = decrypt_sops_document(&document, &identities).await.unwrap();
+
+        assert_eq!(decrypted["token"], "abc");
+        assert_eq!(decrypted["enabled"], true);
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_sops_dotenv() {
+        let (data_key, enc, identities) = data_key();
+        let document = format!(
+            "API_KEY={}\nsops_age__list_0__map_enc={}\nsops_age__list_0__map_recipient=age1test\nsops_lastmodified={LAST_MODIFIED}\nsops_mac={}\nsops_version=3.9.0\n",
+            encrypt_value("key-123", &data_key, "API_KEY:", "str"),
+            enc.replace('\n', "\\n"),
+            encrypt_mac(&["key-123"], &data_key)
+        );
+
+        let decrypted = decrypt_sops_document(&document, &identities).await.unwrap();
+        let data = get_sops_secret_data(&SecretData::default(), &decrypted);
+
+        assert_eq!(data.len(), 1);
+        assert_eq!(data["API_KEY"].0, b"key-123");
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_sops_with_wrong_path_identity_or_mac() {
+        let (data_key, enc, identities) = data_key();
+        let document = |token: &str, mac: &[&str]| {
+            serde_json::json!({
+                token: encrypt_value("abc", &data_key, "token:", "str"),
+                "sops": {
+                    "age": [{ "recipient": "age1test", "enc": enc }],
+                    "lastmodified": LAST_MODIFIED,
+                    "mac": encrypt_mac(mac, &data_key)
+                }
+            })
+            .to_string()
+        };
+
+        assert!(
+            decrypt_sops_document(&document("token", &["abc"]), &identities)
+                .await
+                .is_ok()
+        );
+        assert!(
+            decrypt_sops_document(&document("moved", &["abc"]), &identities)
+                .await
+                .is_err()
+        );
+        assert!(
+            decrypt_sops_document(&document("token", &["abcd"]), &identities)
+                .await
+                .is_err()
+        );
+
+        let (_, _, other_identities) = self::data_key();
+        assert!(
+            decrypt_sops_document(&document("token", &["abc"]), &other_identities)
+                .await
+                .is_err()
+        );
+        assert!(parse_sops_document("plain: yaml").is_err());
+    }
+}
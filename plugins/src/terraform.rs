@@ -0,0 +1,298 @@
+use crate::aws_common::read_s3_url;
+use crate::concurrent::resolve_entries;
+use crate::http_common::{
+    get_allowed_http_hosts, get_http_client, resolve_http_url, ResolvedHttpRequest,
+};
+use crate::registry::Provider;
+use async_trait::async_trait;
+use cached::macros::cached;
+use crd::{Backend, PathMode, RemoteData, RemoteValue, SecretData};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::ByteString;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use utils::value::{get_document_value, get_secret_data, json_value_to_string};
+
+#[derive(Clone, Debug)]
+pub struct Terraform {
+    data: Vec<SecretData>,
+}
+
+#[async_trait]
+impl RemoteValue for Terraform {
+    fn from_backend(backend: &Backend) -> Terraform {
+        Terraform {
+            data: backend.data.clone(),
+        }
+    }
+
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
+            // specific the output value for 1-1 mapping k8s secret key
+            let data = if let (Some(_), Some(remote_path)) =
+                (&secret_data.key, &secret_data.remote_path)
+            {
+                let mode = secret_data.path_mode.clone().unwrap_or_default();
+                get_terraform_output(secret_data.value.clone(), remote_path, &mode)
+                    .await
+                    .map(|data| get_secret_data(secret_data, &data))
+            } else {
+                // insert the whole terraform outputs into k8s secret data
+                get_terraform_outputs_as_secret_data(secret_data.value.clone()).await
+            };
+
+            data.unwrap_or_else(|err| {
+                log::error!("{err}");
+                BTreeMap::new()
+            })
+        })
+        .await
+    }
+}
+
+/// registers the terraform backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Terraform", |backend, _| {
+        Box::new(Terraform::from_backend(backend))
+    }));
+}
+
+/// get the output values of the terraform state stored in s3 or over http
+/// Will cache the result for 60s
+#[cached(ttl = 60)]
+pub async fn get_terraform_outputs(location: String) -> Result<BTreeMap<String, Value>> {
+    let state = read_terraform_state(&location).await?;
+    get_state_outputs(&state)
+}
+
+/// read the state file eg s3://bucket/env/terraform.tfstate or https://host/state/env
+pub async fn read_terraform_state(location: &str) -> Result<Value> {
+    if location.starts_with("s3://") {
+        let body = read_s3_url(location).await?;
+        return Ok(serde_json::from_slice(&body)?);
+    }
+
+    if location.starts_with("http://") || location.starts_with("https://") {
+        let is_state_address = std::env::var("TF_HTTP_ADDRESS")
+            .is_ok_and(|address| is_below_address(location, &address));
+        // the address set by the operator may be internal, the other hosts go through the checks of the http backend
+        let allowed_hosts = match is_state_address {
+            true => vec![reqwest::Url::parse(location)?
+                .host_str()
+                .unwrap_or_default()
+                .to_lowercase()],
+            false => get_allowed_http_hosts(),
+        };
+        let resolved = resolve_http_url(location, &allowed_hosts).await?;
+        let request = get_terraform_http_request(is_state_address);
+
+        let response = get_http_client(location, &request, &resolved)?
+            .send()
+            .await?;
+        if response.status().is_redirection() {
+            return Err(anyhow!("{location} redirects, redirects are not followed"));
+        }
+        return Ok(response.error_for_status()?.json().await?);
+    }
+
+    Err(anyhow!(
+        "unsupported terraform state location {location}, expected s3:// or http(s)://"
+    ))
+}
+
+/// basic auth uses the same environment variables as the terraform http backend,
+/// the credentials are only sent to the states below TF_HTTP_ADDRESS
+pub fn get_terraform_http_request(is_state_address: bool) -> ResolvedHttpRequest {
+    let basic_auth = match std::env::var("TF_HTTP_USERNAME") {
+        Ok(username) if is_state_address => Some((
+            username,
+            std::env::var("TF_HTTP_PASSWORD").unwrap_or_default(),
+        )),
+        _ => None,
+    };
+
+    ResolvedHttpRequest {
+        basic_auth,
+        ..ResolvedHttpRequest::default()
+    }
+}
+
+/// same scheme, host and port as the address and a path below the path of the address
+pub fn is_below_address(location: &str, address: &str) -> bool {
+    let (Ok(location), Ok(address)) = (reqwest::Url::parse(location), reqwest::Url::parse(address))
+    else {
+        return false;
+    };
+    let path = address.path().trim_end_matches('/');
+
+    location.origin() == address.origin()
+        && location
+            .path()
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// get the value of every output in the state, sensitive or not
+pub fn get_state_outputs(state: &Value) -> Result<BTreeMap<String, Value>> {
+    let outputs = state
+        .get("outputs")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("no outputs found in the terraform state"))?;
+
+    Ok(outputs
+        .iter()
+        .map(|(name, output)| {
+            let value = output.get("value").cloned().unwrap_or_default();
+            (name.to_owned(), value)
+        })
+        .collect())
+}
+
+/// find the value of an output, remote_path is the output name
+/// optionally followed by a path into the output value eg database.host or $.database.host
+pub fn find_state_output(
+    outputs: &BTreeMap<String, Value>,
+    remote_path: &str,
+    path_mode: &PathMode,
+) -> Result<String> {
+    let outputs = Value::Object(outputs.clone().into_iter().collect());
+    get_document_value(&outputs, remote_path, path_mode)
+        .map_err(|err| anyhow!("no output found at {remote_path}: {err}"))
+}
+
+/// get the output value from the terraform state
+pub async fn get_terraform_output(
+    location: String,
+    remote_path: &str,
+    path_mode: &PathMode,
+) -> Result<String> {
+    let outputs = get_terraform_outputs(location).await?;
+    find_state_output(&outputs, remote_path, path_mode)
+}
+
+// get the secret data from the whole outputs of the terraform state
+pub async fn get_terraform_outputs_as_secret_data(
+    location: String,
+) -> Result<BTreeMap<String, ByteString>> {
+    let outputs = get_terraform_outputs(location).await?;
+    let mut secrets = BTreeMap::new();
+    for (name, value) in outputs {
+        let output_value = ByteString(json_value_to_string(&value).as_bytes().to_vec());
+        secrets.insert(name, output_value);
+    }
+
+    Ok(secrets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn skip_without_mock_env() -> bool {
+        if crate::aws_common::should_run_aws_integration_tests() {
+            return false;
+        }
+
+        eprintln!("Skipping AWS integration test: TEST_ENV=true is required");
+        true
+    }
+
+    fn sample_state() -> Value {
+        json!({
+            "version": 4,
+            "outputs": {
+                "bucket_name": { "value": "my-bucket", "type": "string" },
+                "db_port": { "value": 5432, "type": "number" },
+                "database": {
+                    "value": { "host": "db.internal", "user": "app" },
+                    "type": ["object", { "host": "string", "user": "string" }],
+                    "sensitive": true
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_get_state_outputs() {
+        let outputs = get_state_outputs(&sample_state()).unwrap();
+
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs.get("bucket_name"), Some(&json!("my-bucket")));
+        assert!(get_state_outputs(&json!({ "version": 4 })).is_err());
+    }
+
+    #[test]
+    fn test_find_state_output() {
+        let outputs = get_state_outputs(&sample_state()).unwrap();
+
+        let dot = &PathMode::DotPath;
+
+        assert_eq!(
+            find_state_output(&outputs, "bucket_name", dot).unwrap(),
+            "my-bucket"
+        );
+        assert_eq!(find_state_output(&outputs, "db_port", dot).unwrap(), "5432");
+        assert_eq!(
+            find_state_output(&outputs, "database.host", dot).unwrap(),
+            "db.internal"
+        );
+        assert!(find_state_output(&outputs, "missing", dot).is_err());
+        assert!(find_state_output(&outputs, "database.missing", dot).is_err());
+
+        assert_eq!(
+            find_state_output(&outputs, "$.database.host", &PathMode::JsonPath).unwrap(),
+            "db.internal"
+        );
+        assert!(find_state_output(&outputs, "$.database.missing", &PathMode::JsonPath).is_err());
+    }
+
+    #[test]
+    fn test_is_below_address() {
+        let address = "https://state.example.com/terraform/";
+
+        assert!(is_below_address(
+            "https://state.example.com/terraform",
+            address
+        ));
+        assert!(is_below_address(
+            "https://state.example.com/terraform/prod",
+            address
+        ));
+        assert!(!is_below_address(
+            "https://state.example.com/terraform-other/prod",
+            address
+        ));
+        assert!(!is_below_address(
+            "https://evil.com/terraform/prod",
+            address
+        ));
+        assert!(!is_below_address(
+            "https://state.example.com.evil.com/terraform/prod",
+            address
+        ));
+        assert!(!is_below_address(
+            "http://state.example.com/terraform/prod",
+            address
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_terraform_outputs() {
+        if skip_without_mock_env() {
+            return;
+        }
+
+        let result = get_terraform_output(
+            "s3://remote-secrets-test/terraform.tfstate".to_string(),
+            "bucket_name",
+            &PathMode::DotPath,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result, "my-bucket");
+    }
+}
@@ -1,11 +1,12 @@
 use crate::aws_common::is_test_env;
+use crate::concurrent::resolve_entries;
+use crate::registry::Provider;
 use async_trait::async_trait;
 use std::collections::BTreeMap;
 
 use cached::macros::cached;
-use k8s_openapi::ByteString;
 
-use crd::{Backend, RemoteValue, SecretData};
+use crd::{Backend, RemoteData, RemoteValue, SecretData};
 use json_dotpath::DotPaths;
 
 use utils::value::get_secret_data;
@@ -25,30 +26,26 @@ impl RemoteValue for Vault {
         }
     }
 
-    async fn get_value(&self) -> BTreeMap<String, ByteString> {
-        let mut secrets = BTreeMap::new();
-
-        for secret_data in self.data.iter() {
+    async fn get_value(&self) -> RemoteData {
+        resolve_entries(&self.data, |secret_data| async move {
             let vault_data = get_vault_value(secret_data.value.clone()).await;
             match vault_data {
-                Ok(vault_data) => {
-                    let data = get_secret_data(secret_data, &vault_data);
-
-                    secrets = data
-                        .into_iter()
-                        .chain(secrets.clone().into_iter())
-                        .collect();
-                }
+                Ok(vault_data) => get_secret_data(secret_data, &vault_data),
                 Err(err) => {
                     log::error!("{err}");
+                    BTreeMap::new()
                 }
             }
-        }
-
-        secrets
+        })
+        .await
     }
 }
 
+/// registers the vault backend in the plugin registry
+pub fn register(providers: &mut Vec<Provider>) {
+    providers.push(Provider::new("Vault", |backend, _| Box::new(Vault::from_backend(backend))));
+}
+
 #[cached(ttl = 60)]
 pub async fn get_vault_value(path: String) -> Result<String> {
     let client = get_vault_client(path.clone())?;
@@ -63,7 +60,41 @@ pub async fn get_vault_value(path: String) -> Result<String> {
     Ok(result)
 }
 
+/// write the value field of the kv v2 secret, read back by get_vault_value
+pub async fn put_vault_value(path: &str, value: &str) -> Result<()> {
+    let endpoint = get_vault_secret_endpoint()?;
+    let token = get_vault_token()?;
+
+    reqwest::Client::new()
+        .post(format!("{endpoint}{path}"))
+        .header("X-Vault-Token", token)
+        .json(&serde_json::json!({ "data": { "value": value } }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// delete every version and the metadata of the kv v2 secret
+pub async fn delete_vault_value(path: &str) -> Result<()> {
+    let endpoint = get_vault_kv_endpoint("metadata")?;
+    let token = get_vault_token()?;
+
+    reqwest::Client::new()
+        .delete(format!("{endpoint}{path}"))
+        .header("X-Vault-Token", token)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
 pub fn get_vault_secret_endpoint() -> Result<String> {
+    get_vault_kv_endpoint("data")
+}
+
+/// data or metadata endpoint of the kv v2 secret engine
+fn get_vault_kv_endpoint(kind: &str) -> Result<String> {
     let local_vault_endpoint = "http://localhost:8200".to_string();
     let url = if is_test_env() {
         std::env::var("VAULT_ADDR").unwrap_or(local_vault_endpoint)
@@ -72,9 +103,9 @@ pub fn get_vault_secret_endpoint() -> Result<String> {
     };
 
     if url.ends_with('/') {
-        Ok(format!("{url}v1/secret/data/"))
+        Ok(format!("{url}v1/secret/{kind}/"))
     } else {
-        Ok(format!("{url}/v1/secret/data/"))
+        Ok(format!("{url}/v1/secret/{kind}/"))
     }
 }
 
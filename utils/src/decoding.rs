@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use crd::{DecodingStrategy, SecretData};
+use k8s_openapi::ByteString;
+use std::collections::BTreeMap;
+
+const PADDING_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, PADDING_INDIFFERENT);
+const BASE64_URL: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, PADDING_INDIFFERENT);
+
+/// decode the fetched values of an entry with its decoding_strategy
+/// a value which does not decode fails the entry, nothing is written instead of garbage
+pub fn decode_secret_data(
+    rsecret_data: &SecretData,
+    data: BTreeMap<String, ByteString>,
+) -> Result<BTreeMap<String, ByteString>> {
+    let strategy = rsecret_data.decoding_strategy.clone().unwrap_or_default();
+    if strategy == DecodingStrategy::None {
+        return Ok(data);
+    }
+
+    data.into_iter()
+        .map(|(key, value)| {
+            // the value itself is not logged, it is a secret
+            let decoded = decode_value(&value.0, &strategy)
+                .map_err(|err| anyhow!("{key} of {} is not valid {err}", rsecret_data.value))?;
+            Ok((key, ByteString(decoded)))
+        })
+        .collect()
+}
+
+/// decode a value, the line breaks of wrapped base64 or hex are ignored
+pub fn decode_value(value: &[u8], strategy: &DecodingStrategy) -> Result<Vec<u8>> {
+    let compact: Vec<u8> = value
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+
+    match strategy {
+        DecodingStrategy::None => Ok(value.to_vec()),
+        DecodingStrategy::Base64 => BASE64.decode(&compact).map_err(|_| anyhow!("base64")),
+        DecodingStrategy::Base64Url => BASE64_URL
+            .decode(&compact)
+            .map_err(|_| anyhow!("url safe base64")),
+        DecodingStrategy::Hex => hex::decode(&compact).map_err(|_| anyhow!("hex")),
+        DecodingStrategy::Auto => Ok(BASE64
+            .decode(&compact)
+            .or_else(|_| BASE64_URL.decode(&compact))
+            .unwrap_or_else(|_| value.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_value() {
+        let keystore = [0xfb, 0xff, 0x00, 0x01];
+
+        assert_eq!(
+            decode_value(b"+/8AAQ==", &DecodingStrategy::Base64).unwrap(),
+            keystore
+        );
+        assert_eq!(
+            decode_value(b"+/8A\nAQ", &DecodingStrategy::Base64).unwrap(),
+            keystore
+        );
+        assert_eq!(
+            decode_value(b"-_8AAQ", &DecodingStrategy::Base64Url).unwrap(),
+            keystore
+        );
+        assert_eq!(
+            decode_value(b"FBFF0001", &DecodingStrategy::Hex).unwrap(),
+            keystore
+        );
+        assert_eq!(
+            decode_value(b"-_8AAQ==", &DecodingStrategy::Auto).unwrap(),
+            keystore
+        );
+        assert_eq!(
+            decode_value(b"p@ss word", &DecodingStrategy::Auto).unwrap(),
+            b"p@ss word"
+        );
+
+        assert!(decode_value(b"-_8AAQ", &DecodingStrategy::Base64).is_err());
+        assert!(decode_value(b"FBFF000", &DecodingStrategy::Hex).is_err());
+    }
+
+    #[test]
+    fn test_decode_secret_data() {
+        let rsecret_data: SecretData =
+            serde_json::from_str(r#"{"value": "certs", "decoding_strategy": "Base64"}"#).unwrap();
+        let data = BTreeMap::from([
+            ("tls.crt".to_string(), ByteString(b"Y2VydA==".to_vec())),
+            ("tls.key".to_string(), ByteString(b"a2V5".to_vec())),
+        ]);
+
+        let decoded = decode_secret_data(&rsecret_data, data.clone()).unwrap();
+        assert_eq!(decoded["tls.crt"].0, b"cert");
+        assert_eq!(decoded["tls.key"].0, b"key");
+
+        let mut invalid = data;
+        invalid.insert("tls.key".to_string(), ByteString(b"not base64!".to_vec()));
+        let err = decode_secret_data(&rsecret_data, invalid).unwrap_err();
+        assert_eq!(err.to_string(), "tls.key of certs is not valid base64");
+    }
+}
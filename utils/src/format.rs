@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+use crd::DataFormat;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::value::parse_dotenv;
+
+/// parse the remote data into a json document, so every format is mapped and queried like json
+/// dotenv and properties documents are flat objects, ini sections are nested objects
+pub fn parse_document(content: &str, format: &DataFormat) -> Result<Value> {
+    let document = match format {
+        DataFormat::Raw => return Err(anyhow!("raw data is not parsed as a document")),
+        DataFormat::Json => serde_json::from_str(content)?,
+        DataFormat::Yaml => serde_yaml::from_str(content)?,
+        DataFormat::Toml => toml_to_json(toml::from_str(content)?),
+        DataFormat::Dotenv => string_map_to_json(parse_dotenv(content)?),
+        DataFormat::Properties => string_map_to_json(parse_properties(content)?),
+        DataFormat::Ini => parse_ini(content)?,
+    };
+
+    Ok(document)
+}
+
+fn string_map_to_json(variables: BTreeMap<String, String>) -> Value {
+    Value::Object(
+        variables
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect(),
+    )
+}
+
+/// datetimes have no json counterpart, they are kept as text
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::String(value),
+        toml::Value::Integer(value) => Value::from(value),
+        toml::Value::Float(value) => Value::from(value),
+        toml::Value::Boolean(value) => Value::Bool(value),
+        toml::Value::Datetime(value) => Value::String(value.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// parse a java properties document, supporting comments, line continuations and escapes
+pub fn parse_properties(content: &str) -> Result<BTreeMap<String, String>> {
+    let mut properties = BTreeMap::new();
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let mut line = line.trim_start().to_owned();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+
+        // an odd number of trailing backslashes continues the line
+        while line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+            line.pop();
+            match lines.next() {
+                Some(next) => line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+
+        let (name, value) = split_property(&line);
+        properties.insert(unescape_property(name)?, unescape_property(value)?);
+    }
+
+    Ok(properties)
+}
+
+/// the name ends at the first unescaped =, : or whitespace
+fn split_property(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if c == '\\' {
+            escaped = true;
+            continue;
+        }
+        if c == '=' || c == ':' || c.is_whitespace() {
+            // whitespace around a single = or : separator is skipped
+            let rest = line[index..].trim_start();
+            let rest = rest.strip_prefix(['=', ':']).unwrap_or(rest);
+            return (&line[..index], rest.trim_start());
+        }
+    }
+
+    (line, "")
+}
+
+fn unescape_property(value: &str) -> Result<String> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('f') => unescaped.push('\u{c}'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                let c = (code.len() == 4)
+                    .then(|| u32::from_str_radix(&code, 16).ok())
+                    .flatten()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| anyhow!("invalid unicode escape \\u{code} in properties"))?;
+                unescaped.push(c);
+            }
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+
+    Ok(unescaped)
+}
+
+/// parse an ini document, the keys of a section are nested under the section name
+pub fn parse_ini(content: &str) -> Result<Value> {
+    let mut document = Map::new();
+    let mut section: Option<String> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.trim().to_owned();
+            document
+                .entry(name.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+            section = Some(name);
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once(['=', ':'])
+            .ok_or_else(|| anyhow!("invalid ini line {}, expected key = value", index + 1))?;
+        let value = Value::String(unquote_ini_value(value.trim()).to_owned());
+
+        let entries = match &section {
+            Some(section) => match document.get_mut(section) {
+                Some(Value::Object(entries)) => entries,
+                _ => {
+                    return Err(anyhow!(
+                        "section {section} clashes with a key of the same name"
+                    ))
+                }
+            },
+            None => &mut document,
+        };
+        entries.insert(name.trim().to_owned(), value);
+    }
+
+    Ok(Value::Object(document))
+}
+
+fn unquote_ini_value(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_properties() {
+        let content = r#"
+        # database settings
+        ! also a comment
+        db.host = db.internal
+        db.user:app
+        db.password p@ss\=word
+        db.hosts = a.internal, \
+                   b.internal
+        greeting = café\tbar
+        empty
+        "#;
+
+        let properties = parse_properties(content).unwrap();
+
+        assert_eq!(properties.len(), 6);
+        assert_eq!(properties["db.host"], "db.internal");
+        assert_eq!(properties["db.user"], "app");
+        assert_eq!(properties["db.password"], "p@ss=word");
+        assert_eq!(properties["db.hosts"], "a.internal, b.internal");
+        assert_eq!(properties["greeting"], "café\tbar");
+        assert_eq!(properties["empty"], "");
+        assert!(parse_properties("key = \\u12").is_err());
+    }
+
+    #[test]
+    fn test_parse_ini() {
+        let content = r#"
+        ; global settings
+        mode = production
+
+        [database]
+        host = db.internal
+        password = "p@ss word"
+
+        [cache]
+        host: cache.internal
+        "#;
+
+        let document = parse_ini(content).unwrap();
+
+        assert_eq!(document["mode"], "production");
+        assert_eq!(document["database"]["host"], "db.internal");
+        assert_eq!(document["database"]["password"], "p@ss word");
+        assert_eq!(document["cache"]["host"], "cache.internal");
+        assert!(parse_ini("[database]\nhost").is_err());
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let yaml = "database:\n  host: db.internal\n  port: 5432\n";
+        let toml =
+            "[database]\nhost = \"db.internal\"\nport = 5432\nrotated = 2026-01-01T00:00:00Z\n";
+
+        let document = parse_document(yaml, &DataFormat::Yaml).unwrap();
+        assert_eq!(document["database"]["port"], 5432);
+
+        let document = parse_document(toml, &DataFormat::Toml).unwrap();
+        assert_eq!(document["database"]["host"], "db.internal");
+        assert_eq!(document["database"]["rotated"], "2026-01-01T00:00:00Z");
+
+        let document = parse_document("DB_HOST=db.internal", &DataFormat::Dotenv).unwrap();
+        assert_eq!(document["DB_HOST"], "db.internal");
+
+        assert!(parse_document("database: [", &DataFormat::Yaml).is_err());
+        assert!(parse_document("{}", &DataFormat::Raw).is_err());
+        assert!(parse_document("database = ", &DataFormat::Toml).is_err());
+    }
+}
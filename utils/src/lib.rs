@@ -1,3 +1,6 @@
+pub mod decoding;
+pub mod format;
 pub mod log;
 pub mod metrics;
+pub mod rewrite;
 pub mod value;
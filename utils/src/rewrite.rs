@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Result};
+use crd::{KeyCase, KeyRewrite};
+use k8s_openapi::ByteString;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// rewrite the keys of the data of a backend with the rules in order
+pub fn rewrite_keys(
+    data: BTreeMap<String, ByteString>,
+    rules: &[KeyRewrite],
+) -> Result<BTreeMap<String, ByteString>> {
+    let mut data = data;
+    for rule in rules {
+        data = apply_rewrite(data, rule)?;
+    }
+
+    Ok(data)
+}
+
+fn compile_regex(source: &str) -> Result<Regex> {
+    Regex::new(source).map_err(|err| anyhow!("invalid rewrite regex {source}: {err}"))
+}
+
+fn apply_rewrite(
+    data: BTreeMap<String, ByteString>,
+    rule: &KeyRewrite,
+) -> Result<BTreeMap<String, ByteString>> {
+    let include = rule.include.as_deref().map(compile_regex).transpose()?;
+    let exclude = rule.exclude.as_deref().map(compile_regex).transpose()?;
+    let regexp = match &rule.regexp {
+        Some(regexp) => Some((compile_regex(&regexp.source)?, regexp.target.as_str())),
+        None => None,
+    };
+
+    let mut rewritten = BTreeMap::new();
+    for (key, value) in data {
+        if include
+            .as_ref()
+            .is_some_and(|include| !include.is_match(&key))
+            || exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(&key))
+        {
+            continue;
+        }
+
+        let mut new_key = key.clone();
+        if let Some((source, target)) = &regexp {
+            new_key = source.replace_all(&new_key, *target).into_owned();
+        }
+        if let Some(KeyCase::UpperSnakeCase) = rule.case {
+            new_key = to_upper_snake_case(&new_key);
+        }
+        if let Some(prefix) = &rule.prefix {
+            new_key = format!("{prefix}{new_key}");
+        }
+        if let Some(suffix) = &rule.suffix {
+            new_key = format!("{new_key}{suffix}");
+        }
+
+        if !is_valid_secret_key(&new_key) {
+            log::error!(
+                "{key} is rewritten to {new_key:?}, which is not a valid secret key, skipping it"
+            );
+            continue;
+        }
+        // the keys are visited in order, so the first key rewritten to the same key is kept
+        if rewritten.contains_key(&new_key) {
+            log::warn!("{key} is rewritten to {new_key} like a previous key, skipping it");
+            continue;
+        }
+
+        rewritten.insert(new_key, value);
+    }
+
+    Ok(rewritten)
+}
+
+/// secret keys are made of alphanumerics, -, _ and .
+pub fn is_valid_secret_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 253
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// DbEndpoint, db-endpoint, dbEndpoint and db.endpoint become DB_ENDPOINT, HTTPServer becomes HTTP_SERVER
+pub fn to_upper_snake_case(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+
+    for (index, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        if c.is_uppercase() && !word.is_empty() {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            // a word starts on a lowercase to uppercase change, or at the end of an acronym
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(*c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+        .iter()
+        .map(|word| word.to_uppercase())
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crd::RegexpRewrite;
+
+    fn sample_data(keys: &[&str]) -> BTreeMap<String, ByteString> {
+        keys.iter()
+            .map(|key| (key.to_string(), ByteString(key.as_bytes().to_vec())))
+            .collect()
+    }
+
+    #[test]
+    fn test_to_upper_snake_case() {
+        for (key, expected) in [
+            ("DbEndpoint", "DB_ENDPOINT"),
+            ("db-endpoint", "DB_ENDPOINT"),
+            ("dbEndpoint", "DB_ENDPOINT"),
+            ("db.endpoint", "DB_ENDPOINT"),
+            ("DB_ENDPOINT", "DB_ENDPOINT"),
+            ("HTTPServer", "HTTP_SERVER"),
+            ("Db2Endpoint", "DB2_ENDPOINT"),
+            ("/apps/db//host", "APPS_DB_HOST"),
+        ] {
+            assert_eq!(to_upper_snake_case(key), expected);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_keys_in_order() {
+        let data = sample_data(&["DbEndpoint", "DbPort", "InternalArn"]);
+        let rules = vec![
+            KeyRewrite {
+                exclude: Some("^Internal".into()),
+                regexp: Some(RegexpRewrite {
+                    source: "^Db(.*)$".into(),
+                    target: "Database${1}".into(),
+                }),
+                ..KeyRewrite::default()
+            },
+            KeyRewrite {
+                case: Some(KeyCase::UpperSnakeCase),
+                prefix: Some("APP_".into()),
+                ..KeyRewrite::default()
+            },
+        ];
+
+        let rewritten = rewrite_keys(data, &rules).unwrap();
+        assert_eq!(
+            rewritten.keys().collect::<Vec<&String>>(),
+            vec!["APP_DATABASE_ENDPOINT", "APP_DATABASE_PORT"]
+        );
+        assert_eq!(rewritten["APP_DATABASE_PORT"].0, b"DbPort");
+    }
+
+    #[test]
+    fn test_rewrite_keys_include_and_collisions() {
+        let data = sample_data(&["db-host", "db.host", "cache.host"]);
+        let rules = vec![KeyRewrite {
+            include: Some("^db".into()),
+            case: Some(KeyCase::UpperSnakeCase),
+            suffix: Some("_VALUE".into()),
+            ..KeyRewrite::default()
+        }];
+
+        let rewritten = rewrite_keys(data, &rules).unwrap();
+        assert_eq!(rewritten.len(), 1);
+        assert_eq!(rewritten["DB_HOST_VALUE"].0, b"db-host");
+    }
+
+    #[test]
+    fn test_rewrite_keys_errors() {
+        let rules = vec![KeyRewrite {
+            include: Some("([".into()),
+            ..KeyRewrite::default()
+        }];
+        assert!(rewrite_keys(sample_data(&["key"]), &rules).is_err());
+
+        let rules = vec![KeyRewrite {
+            prefix: Some("app/".into()),
+            ..KeyRewrite::default()
+        }];
+        assert!(rewrite_keys(sample_data(&["key"]), &rules)
+            .unwrap()
+            .is_empty());
+    }
+}
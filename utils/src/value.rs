@@ -1,78 +1,350 @@
-use anyhow::Result;
-use crd::SecretData;
+use anyhow::{anyhow, Result};
+use crd::{DataFormat, JsonFlatten, PathMode, SecretData};
 use json_dotpath::DotPaths;
+use jsonpath_rust::JsonPath;
 use k8s_openapi::ByteString;
 use serde_json::Value;
 use std::collections::BTreeMap;
 
-pub fn get_json_string_nested_value(json_string: &str, path: &str) -> Result<String> {
+use crate::format::parse_document;
+use crate::rewrite::is_valid_secret_key;
+
+/// the value matched by the jsonpath expression, several matches are rendered as a json array
+pub fn get_json_string_json_path_value(json_string: &str, path: &str) -> Result<String> {
     let json: Value = serde_json::from_str(json_string)?;
+    get_document_value(&json, path, &PathMode::JsonPath)
+}
+
+/// the value at the path of a parsed document, scalars are rendered as text and objects or arrays as json
+/// a dot path matches a key named like it first, eg db.host of a properties document
+pub fn get_document_value(document: &Value, path: &str, mode: &PathMode) -> Result<String> {
+    match mode {
+        PathMode::DotPath => {
+            let value = match document.get(path) {
+                Some(value) => Some(value.clone()),
+                None => document.dot_get::<Value>(path).unwrap_or_default(),
+            };
 
-    let result = json
-        .dot_get::<String>(path)
-        .unwrap_or_default()
-        .unwrap_or_default();
+            match value {
+                None | Some(Value::Null) => Err(anyhow!("nothing found at the path {path}")),
+                Some(value) => Ok(json_value_to_string(&value)),
+            }
+        }
+        PathMode::JsonPath => {
+            let matches = document
+                .query(path)
+                .map_err(|err| anyhow!("invalid json path {path}: {err}"))?;
 
-    Ok(result)
+            match matches.as_slice() {
+                [] => Err(anyhow!("the json path {path} matches nothing")),
+                [Value::Null] => Err(anyhow!("nothing found at the path {path}")),
+                [value] => Ok(json_value_to_string(value)),
+                values => Ok(
+                    Value::Array(values.iter().map(|value| (*value).clone()).collect()).to_string(),
+                ),
+            }
+        }
+    }
+}
+
+/// render a json value as secret text, strings are kept as is and anything else is serialised
+pub fn json_value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.to_owned(),
+        _ => value.to_string(),
+    }
 }
 
-pub fn get_json_string_as_secret_data(json_string: &str) -> Result<BTreeMap<String, ByteString>> {
+const DEFAULT_JSON_SEPARATOR: &str = ".";
+
+/// map every field of a json object, or item of a json array, to a secret key
+/// nested values are flattened into keys joined by the separator eg db.host, or kept as json strings
+pub fn get_json_string_as_secret_data(
+    json_string: &str,
+    flatten: &JsonFlatten,
+) -> Result<BTreeMap<String, ByteString>> {
     let json: Value = serde_json::from_str(json_string)?;
+    get_document_as_secret_data(&json, flatten)
+}
+
+/// map every field of a parsed document to a secret key, like a json object
+/// null fields are left out, and so are fields whose key is not a valid secret key
+pub fn get_document_as_secret_data(
+    document: &Value,
+    flatten: &JsonFlatten,
+) -> Result<BTreeMap<String, ByteString>> {
+    if !document.is_object() && !document.is_array() {
+        // the value itself is not logged, it is a secret
+        return Err(anyhow!(
+            "expected an object or array without key, got a {}",
+            json_type_name(document)
+        ));
+    }
+
+    let separator = flatten
+        .separator
+        .as_deref()
+        .unwrap_or(DEFAULT_JSON_SEPARATOR);
+    if !separator.is_empty() && !is_valid_secret_key(separator) {
+        return Err(anyhow!(
+            "invalid flatten separator {separator:?}, expected alphanumerics, -, _ or ."
+        ));
+    }
+    let nested_as_json = flatten.nested_as_json.unwrap_or_default();
+
     let mut secrets = BTreeMap::new();
-    let json_as_hashmap = json.as_object().unwrap();
-    for (key, value) in json_as_hashmap {
-        secrets.insert(
-            key.to_owned(),
-            ByteString(value.as_str().unwrap().to_owned().as_bytes().to_vec()),
-        );
+    for (key, value) in json_children(document) {
+        flatten_json_value(key, value, separator, nested_as_json, &mut secrets);
     }
 
     Ok(secrets)
 }
 
-// TODO: a better way error handling
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// fields of an object, or items of an array keyed by their index
+fn json_children(value: &Value) -> Vec<(String, &Value)> {
+    match value {
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (index.to_string(), value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn flatten_json_value(
+    key: String,
+    value: &Value,
+    separator: &str,
+    nested_as_json: bool,
+    secrets: &mut BTreeMap<String, ByteString>,
+) {
+    match value {
+        Value::Object(_) | Value::Array(_) if !nested_as_json => {
+            for (child_key, child) in json_children(value) {
+                let child_key = format!("{key}{separator}{child_key}");
+                flatten_json_value(child_key, child, separator, nested_as_json, secrets);
+            }
+        }
+        Value::Null => {}
+        _ if !is_valid_secret_key(&key) => {
+            log::error!("{key:?} is not a valid secret key, skipping it");
+        }
+        _ => {
+            // a flattened key may collide with a field named like it, the first one is kept
+            secrets
+                .entry(key)
+                .or_insert_with(|| ByteString(json_value_to_string(value).into_bytes()));
+        }
+    }
+}
+
+/// parse a dotenv document, supporting comments, export prefixes and quoted values
+pub fn parse_dotenv(content: &str) -> Result<BTreeMap<String, String>> {
+    Ok(parse_dotenv_lines(content)?.into_iter().collect())
+}
+
+/// variables of a dotenv document in the order of their lines
+pub fn parse_dotenv_lines(content: &str) -> Result<Vec<(String, String)>> {
+    let mut variables = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("invalid dotenv line {}, expected KEY=value", index + 1))?;
+
+        variables.push((name.trim().to_owned(), unquote_dotenv_value(value.trim())));
+    }
+
+    Ok(variables)
+}
+
+fn unquote_dotenv_value(value: &str) -> String {
+    if let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        return unescape_dotenv_value(inner);
+    }
+
+    if let Some(inner) = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+    {
+        return inner.to_owned();
+    }
+
+    // unquoted values may be followed by a comment
+    match value.split_once(" #") {
+        Some((value, _)) => value.trim_end().to_owned(),
+        None => value.to_owned(),
+    }
+}
+
+/// unescape \\, \" and \n of a double quoted value in one pass, other escapes are kept as is
+fn unescape_dotenv_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(escaped @ ('\\' | '"')) => unescaped.push(escaped),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// get the secret data from a binary payload, raw payloads are kept as is
+/// and anything else has to be utf-8 text
+pub fn get_secret_bytes_data(
+    rsecret_data: &SecretData,
+    value_bytes: &[u8],
+) -> BTreeMap<String, ByteString> {
+    let is_raw = match rsecret_data.format {
+        Some(DataFormat::Raw) => true,
+        Some(_) => false,
+        None => rsecret_data.key.is_some() && !rsecret_data.is_json_string.unwrap_or_default(),
+    };
+
+    if is_raw {
+        let mut secrets = BTreeMap::new();
+        match &rsecret_data.key {
+            Some(key) => {
+                secrets.insert(key.to_owned(), ByteString(value_bytes.to_vec()));
+            }
+            None => log::error!("the raw data of {} needs a key", rsecret_data.value),
+        }
+        return secrets;
+    }
+
+    match std::str::from_utf8(value_bytes) {
+        Ok(value_string) => get_secret_data(rsecret_data, value_string),
+        Err(e) => {
+            log::error!("{e}");
+            BTreeMap::new()
+        }
+    }
+}
+
+/// format of the remote data, format takes precedence over is_json_string
+pub fn get_data_format(rsecret_data: &SecretData) -> DataFormat {
+    match &rsecret_data.format {
+        Some(format) => format.clone(),
+        None if rsecret_data.is_json_string.unwrap_or_default() => DataFormat::Json,
+        None => DataFormat::Raw,
+    }
+}
+
+/// get the secret data from the remote data
+/// with key and remote_path the value at the path of the parsed document is mapped to the key,
+/// with key only the remote data is kept as is, without key every field of the document is mapped
 pub fn get_secret_data(
     rsecret_data: &SecretData,
     value_string: &str,
 ) -> BTreeMap<String, ByteString> {
+    get_document_secret_data(rsecret_data, value_string).unwrap_or_else(|e| {
+        log::error!("{e}");
+        BTreeMap::new()
+    })
+}
+
+fn get_document_secret_data(
+    rsecret_data: &SecretData,
+    value_string: &str,
+) -> Result<BTreeMap<String, ByteString>> {
+    let format = get_data_format(rsecret_data);
     let mut secrets = BTreeMap::new();
-    if rsecret_data.key.is_some() {
-        let key = rsecret_data.key.clone().unwrap();
-
-        if rsecret_data.is_json_string.unwrap_or_default() {
-            if rsecret_data.remote_path.is_some() {
-                let value = get_json_string_nested_value(
-                    value_string,
-                    &rsecret_data.remote_path.clone().unwrap(),
-                );
-
-                match value {
-                    Ok(value) => {
-                        if !value.is_empty() {
-                            secrets.insert(key, ByteString(value.as_bytes().to_vec()));
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("{e}");
-                    }
-                }
-            }
-        } else {
-            secrets.insert(key, ByteString(value_string.as_bytes().to_vec()));
-        }
-    } else {
-        let data = get_json_string_as_secret_data(value_string);
-        match data {
-            Ok(data) => {
-                secrets = data;
-            }
-            Err(e) => {
-                log::error!("{e}");
-            }
+
+    match (&rsecret_data.key, &rsecret_data.remote_path) {
+        (Some(_), Some(_)) if format != DataFormat::Raw => {
+            let document = parse_document(value_string, &format)?;
+            secrets = get_parsed_document_secret_data(rsecret_data, &document)?;
+        }
+        (Some(key), _) => {
+            secrets.insert(key.to_owned(), ByteString(value_string.as_bytes().to_vec()));
+        }
+        (None, _) => {
+            // without key the remote data is a json document, unless the format is set
+            let format = match (&rsecret_data.format, format) {
+                (Some(DataFormat::Raw), _) => return Err(raw_data_without_key(rsecret_data)),
+                (None, DataFormat::Raw) => DataFormat::Json,
+                (_, format) => format,
+            };
+            let document = parse_document(value_string, &format)?;
+            secrets = get_parsed_document_secret_data(rsecret_data, &document)?;
         }
     }
 
-    secrets
+    Ok(secrets)
+}
+
+/// get the secret data from a document parsed by the backend, eg a decrypted sops document
+/// with key and remote_path the value at the path is mapped to the key, with key only the document as json,
+/// without key every field of the document is mapped
+pub fn get_parsed_document_secret_data(
+    rsecret_data: &SecretData,
+    document: &Value,
+) -> Result<BTreeMap<String, ByteString>> {
+    let mut secrets = BTreeMap::new();
+
+    match (&rsecret_data.key, &rsecret_data.remote_path) {
+        (Some(key), Some(remote_path)) => {
+            let mode = rsecret_data.path_mode.clone().unwrap_or_default();
+            let value = get_document_value(document, remote_path, &mode)?;
+            secrets.insert(key.to_owned(), ByteString(value.into_bytes()));
+        }
+        (Some(key), None) => {
+            secrets.insert(
+                key.to_owned(),
+                ByteString(document.to_string().into_bytes()),
+            );
+        }
+        (None, _) if rsecret_data.format == Some(DataFormat::Raw) => {
+            return Err(raw_data_without_key(rsecret_data));
+        }
+        (None, _) => {
+            let flatten = rsecret_data.flatten.clone().unwrap_or_default();
+            secrets = get_document_as_secret_data(document, &flatten)?;
+        }
+    }
+
+    Ok(secrets)
+}
+
+fn raw_data_without_key(rsecret_data: &SecretData) -> anyhow::Error {
+    anyhow!("the raw data of {} needs a key", rsecret_data.value)
 }
 
 pub fn merge_secret_data(
@@ -87,7 +359,46 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_json_string_nested_value() {
+    fn test_get_document_value() {
+        let document: Value = serde_json::from_str(
+            r#"
+        {
+            "name": "John Doe",
+            "age": 43,
+            "spouse": null,
+            "phones": [
+                "+44 1234567",
+                "+44 2345678"
+            ],
+            "address": {
+                "street": "Downing Street 10"
+            }
+        }"#,
+        )
+        .unwrap();
+
+        for (dot_path, json_path, expected) in [
+            ("name", "$.name", "John Doe"),
+            ("address.street", "$.address.street", "Downing Street 10"),
+            ("phones.0", "$.phones[0]", "+44 1234567"),
+            ("age", "$.age", "43"),
+            ("address", "$.address", r#"{"street":"Downing Street 10"}"#),
+        ] {
+            let value = get_document_value(&document, dot_path, &PathMode::DotPath).unwrap();
+            assert_eq!(value, expected);
+            let value = get_document_value(&document, json_path, &PathMode::JsonPath).unwrap();
+            assert_eq!(value, expected);
+        }
+
+        // missing and null values are errors in both modes
+        for (dot_path, json_path) in [("notExisted", "$.notExisted"), ("spouse", "$.spouse")] {
+            assert!(get_document_value(&document, dot_path, &PathMode::DotPath).is_err());
+            assert!(get_document_value(&document, json_path, &PathMode::JsonPath).is_err());
+        }
+    }
+
+    #[test]
+    fn test_get_secret_data_at_dot_path() {
         let data = r#"
         {
             "name": "John Doe",
@@ -101,15 +412,225 @@ mod tests {
             }
         }"#;
 
-        let name: String = get_json_string_nested_value(data, "name").unwrap();
-        let street: String = get_json_string_nested_value(data, "address.street").unwrap();
-        let phone1: String = get_json_string_nested_value(data, "phones.0").unwrap();
-        let not_existed: String = get_json_string_nested_value(data, "notExisted").unwrap();
+        let path = |path: &str| {
+            let secret_data = SecretData {
+                key: Some("key".to_string()),
+                remote_path: Some(path.to_string()),
+                is_json_string: Some(true),
+                ..SecretData::default()
+            };
+            get_secret_data(&secret_data, data)
+                .get("key")
+                .map(|value| String::from_utf8(value.0.clone()).unwrap())
+        };
+
+        assert_eq!(path("name").unwrap(), "John Doe");
+        assert_eq!(path("address.street").unwrap(), "Downing Street 10");
+        assert_eq!(path("phones.0").unwrap(), "+44 1234567");
+        assert_eq!(path("notExisted"), None);
+
+        assert_eq!(path("age").unwrap(), "43");
+        assert_eq!(
+            path("address").unwrap(),
+            r#"{"street":"Downing Street 10"}"#
+        );
+
+        // raw data is kept as is, and has no fields to map without a key
+        let raw = SecretData {
+            format: Some(DataFormat::Raw),
+            ..SecretData::default()
+        };
+        assert!(get_secret_data(&raw, data).is_empty());
+        assert!(get_secret_bytes_data(&raw, data.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn test_get_json_string_json_path_value() {
+        let data = r#"
+        {
+            "users": [
+                { "name": "alice", "role": "admin", "uid": 1000 },
+                { "name": "bob", "role": "dev", "uid": 1001 },
+                { "name": "carol", "role": "admin", "uid": 1002 }
+            ]
+        }"#;
+
+        let path = |path| get_json_string_json_path_value(data, path);
+        assert_eq!(path("$.users[1].name").unwrap(), "bob");
+        assert_eq!(path("$.users[0].uid").unwrap(), "1000");
+        assert_eq!(
+            path("$.users[?(@.role == 'admin')].name").unwrap(),
+            r#"["alice","carol"]"#
+        );
+        assert_eq!(path("$.users[*].uid").unwrap(), "[1000,1001,1002]");
+        assert_eq!(
+            path("$.users[2]").unwrap(),
+            r#"{"name":"carol","role":"admin","uid":1002}"#
+        );
+
+        assert_eq!(
+            path("$.groups").unwrap_err().to_string(),
+            "the json path $.groups matches nothing"
+        );
+        assert!(path("$.users[?(@.role ==")
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid json path"));
+
+        let secret_data: SecretData = serde_json::from_str(
+            r#"{"value": "x", "is_json_string": true, "key": "admin", "remote_path": "$.users[0].name", "path_mode": "JsonPath"}"#,
+        )
+        .unwrap();
+        assert_eq!(get_secret_data(&secret_data, data)["admin"].0, b"alice");
+    }
+
+    #[test]
+    fn test_get_json_string_as_secret_data() {
+        let data = r#"
+        {
+            "db": { "host": "db.internal", "port": 5432, "replicas": ["a", "b"] },
+            "debug": false,
+            "token": "s3cr3t",
+            "empty": null,
+            "not a key": "dropped"
+        }"#;
+
+        let result = get_json_string_as_secret_data(data, &JsonFlatten::default()).unwrap();
+        assert_eq!(result.len(), 6);
+        assert_eq!(result["db.host"].0, b"db.internal");
+        assert_eq!(result["db.port"].0, b"5432");
+        assert_eq!(result["db.replicas.1"].0, b"b");
+        assert_eq!(result["debug"].0, b"false");
+        assert!(!result.contains_key("empty"));
+        assert!(!result.contains_key("not a key"));
+
+        let flatten = JsonFlatten {
+            separator: Some("_".into()),
+            nested_as_json: None,
+        };
+        let result = get_json_string_as_secret_data(data, &flatten).unwrap();
+        assert_eq!(result["db_host"].0, b"db.internal");
+
+        let flatten = JsonFlatten {
+            separator: Some("/".into()),
+            nested_as_json: None,
+        };
+        assert!(get_json_string_as_secret_data(data, &flatten).is_err());
+
+        let flatten = JsonFlatten {
+            separator: None,
+            nested_as_json: Some(true),
+        };
+        let result = get_json_string_as_secret_data(data, &flatten).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result["db"].0,
+            br#"{"host":"db.internal","port":5432,"replicas":["a","b"]}"#
+        );
+
+        let result =
+            get_json_string_as_secret_data(r#"["a", {"b": 1}]"#, &JsonFlatten::default()).unwrap();
+        assert_eq!(result["1.b"].0, b"1");
+
+        let err =
+            get_json_string_as_secret_data(r#""s3cr3t""#, &JsonFlatten::default()).unwrap_err();
+        assert!(!err.to_string().contains("s3cr3t"));
+        assert!(get_json_string_as_secret_data("{", &JsonFlatten::default()).is_err());
+    }
+
+    #[test]
+    fn test_json_value_to_string() {
+        assert_eq!(json_value_to_string(&serde_json::json!("text")), "text");
+        assert_eq!(json_value_to_string(&serde_json::json!(42)), "42");
+        assert_eq!(
+            json_value_to_string(&serde_json::json!({"a": [1, true]})),
+            r#"{"a":[1,true]}"#
+        );
+    }
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+        # database settings
+        DB_HOST=db.internal
+        export DB_USER = app # inline comment
+        DB_PASSWORD="p@ss word\n2"
+        DB_NAME='app #1'
+        DB_PATH="C:\\new \"dir\" \t"
+        "#;
+
+        let variables = parse_dotenv(content).unwrap();
+
+        assert_eq!(variables.len(), 5);
+        assert_eq!(variables["DB_HOST"], "db.internal");
+        assert_eq!(variables["DB_USER"], "app");
+        assert_eq!(variables["DB_PASSWORD"], "p@ss word\n2");
+        assert_eq!(variables["DB_NAME"], "app #1");
+        assert_eq!(variables["DB_PATH"], r#"C:\new "dir" \t"#);
+        assert!(parse_dotenv("NOT_A_VARIABLE").is_err());
+    }
+
+    #[test]
+    fn test_get_dotenv_secret_data() {
+        let content = "DB_HOST=db.internal\nDB_USER=app\n";
+        let all: SecretData =
+            serde_json::from_str(r#"{"value": "x", "format": "Dotenv"}"#).unwrap();
+        let one: SecretData = serde_json::from_str(
+            r#"{"value": "x", "format": "Dotenv", "key": "host", "remote_path": "DB_HOST"}"#,
+        )
+        .unwrap();
+
+        let result = get_secret_data(&all, content);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["DB_USER"].0, b"app");
+
+        let result = get_secret_data(&one, content);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result["host"].0, b"db.internal");
+    }
+
+    #[test]
+    fn test_get_document_secret_data() {
+        let yaml = "database:\n  host: db.internal\n  port: 5432\n";
+        let one: SecretData = serde_json::from_str(
+            r#"{"value": "x", "format": "Yaml", "key": "port", "remote_path": "database.port"}"#,
+        )
+        .unwrap();
+        let result = get_secret_data(&one, yaml);
+        assert_eq!(result["port"].0, b"5432");
+
+        let properties = "db.host = db.internal\ndb.user = app\n";
+        let all: SecretData =
+            serde_json::from_str(r#"{"value": "x", "format": "Properties"}"#).unwrap();
+        let result = get_secret_data(&all, properties);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result["db.host"].0, b"db.internal");
+
+        let one: SecretData = serde_json::from_str(
+            r#"{"value": "x", "format": "Properties", "key": "user", "remote_path": "db.user"}"#,
+        )
+        .unwrap();
+        assert_eq!(get_secret_data(&one, properties)["user"].0, b"app");
+
+        let missing: SecretData = serde_json::from_str(
+            r#"{"value": "x", "format": "Yaml", "key": "user", "remote_path": "database.user"}"#,
+        )
+        .unwrap();
+        assert!(get_secret_data(&missing, yaml).is_empty());
+    }
+
+    #[test]
+    fn test_get_secret_bytes_data() {
+        let raw: SecretData = serde_json::from_str(r#"{"value": "x", "key": "keystore"}"#).unwrap();
+        let json: SecretData = serde_json::from_str(r#"{"value": "x"}"#).unwrap();
+
+        let result = get_secret_bytes_data(&raw, &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(result["keystore"].0, vec![0xde, 0xad, 0xbe, 0xef]);
+
+        let result = get_secret_bytes_data(&json, br#"{"user": "app"}"#);
+        assert_eq!(result["user"].0, b"app");
 
-        assert_eq!(name, "John Doe");
-        assert_eq!(street, "Downing Street 10");
-        assert_eq!(phone1, "+44 1234567");
-        assert_eq!(not_existed, "");
+        assert!(get_secret_bytes_data(&json, &[0xff, 0xfe]).is_empty());
     }
 
     #[test]